@@ -0,0 +1,360 @@
+use bit_vec::BitVec;
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashMap;
+use std::hash::{ Hash, Hasher };
+
+// cells are dense indices `0..len`; unbounded worlds (like day 17) pad their bounds by
+// the number of generations they run, since nothing spreads faster than one cell a step
+pub trait Rule {
+    type State: Copy + Eq + Hash;
+
+    // must be symmetric: a cell's next state may only change when one of its neighbours did
+    fn neighbours(&self, cell: usize) -> &[usize];
+
+    // `neighbours` holds the states of `self.neighbours(cell)`, in the same order
+    fn next(&self, cell: usize, state: Self::State, neighbours: &[Self::State]) -> Self::State;
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Cycle {
+    // first generation of the repeating sequence
+    pub start: usize,
+    // 1 for a fixed point
+    pub period: usize,
+}
+
+fn cell_hash<S: Hash>(cell: usize, state: &S) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    (cell, state).hash(&mut hasher);
+    hasher.finish()
+}
+
+#[derive(Debug, Clone)]
+pub struct Automaton<R: Rule> {
+    rule: R,
+    current: Vec<R::State>,
+    next: Vec<R::State>,
+    // cells that may change in the next step
+    frontier: Vec<usize>,
+    in_frontier: BitVec,
+    changed: Vec<usize>,
+    generation: usize,
+    // xor of every cell's hash, kept up to date as cells change
+    hash: u64,
+    // generations by hash; hashes can collide, so a match is confirmed by replaying from `initial`
+    seen: HashMap<u64, Vec<usize>>,
+    initial: Vec<R::State>,
+    cycle: Option<Cycle>,
+    scratch: Vec<R::State>,
+}
+
+impl<R: Rule> Automaton<R> {
+    pub fn new(rule: R, initial: Vec<R::State>) -> Automaton<R> {
+        let hash = initial.iter()
+            .enumerate()
+            .fold(0, |hash, (cell, state)| hash ^ cell_hash(cell, state));
+
+        let mut seen = HashMap::new();
+        seen.insert(hash, vec![0]);
+
+        Automaton {
+            rule,
+            next: initial.clone(),
+            current: initial.clone(),
+            frontier: (0..initial.len()).collect(),
+            in_frontier: BitVec::from_elem(initial.len(), true),
+            initial,
+            changed: Vec::new(),
+            generation: 0,
+            hash,
+            seen,
+            cycle: None,
+            scratch: Vec::new(),
+        }
+    }
+
+    pub fn rule(&self) -> &R {
+        &self.rule
+    }
+
+    pub fn states(&self) -> &[R::State] {
+        &self.current
+    }
+
+    pub fn get(&self, cell: usize) -> Option<R::State> {
+        self.current.get(cell).cloned()
+    }
+
+    pub fn len(&self) -> usize {
+        self.current.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.current.is_empty()
+    }
+
+    pub fn generation(&self) -> usize {
+        self.generation
+    }
+
+    // cells that changed in the last step
+    pub fn changed(&self) -> &[usize] {
+        &self.changed
+    }
+
+    pub fn is_stable(&self) -> bool {
+        self.frontier.is_empty()
+    }
+
+    // set once a step reaches a state seen before
+    pub fn cycle(&self) -> Option<Cycle> {
+        self.cycle
+    }
+
+    // advances one generation, returning how many cells changed
+    pub fn step(&mut self) -> usize {
+        self.changed.clear();
+
+        // cells outside the frontier already hold the same state in both buffers
+        for cell in self.frontier.iter().cloned() {
+            self.scratch.clear();
+            for neighbour in self.rule.neighbours(cell) {
+                self.scratch.push(self.current[*neighbour]);
+            }
+
+            let state = self.current[cell];
+            let next = self.rule.next(cell, state, &self.scratch);
+            self.next[cell] = next;
+
+            if next != state {
+                self.changed.push(cell);
+                self.hash ^= cell_hash(cell, &state) ^ cell_hash(cell, &next);
+            }
+        }
+
+        std::mem::swap(&mut self.current, &mut self.next);
+
+        for cell in self.frontier.drain(..) {
+            self.in_frontier.set(cell, false);
+        }
+
+        for cell in self.changed.iter().cloned() {
+            for c in Some(cell).iter().chain(self.rule.neighbours(cell)) {
+                if !self.in_frontier[*c] {
+                    self.in_frontier.set(*c, true);
+                    self.frontier.push(*c);
+                }
+            }
+        }
+
+        self.generation += 1;
+        if self.cycle.is_none() {
+            let start = self.seen.get(&self.hash).and_then(|generations| self.replay(generations));
+            match start {
+                Some(start) => self.cycle = Some(Cycle { start, period: self.generation - start }),
+                None => self.seen.entry(self.hash).or_default().push(self.generation),
+            }
+        }
+
+        self.changed.len()
+    }
+
+    // the first of `generations` (in increasing order) whose states equal the current ones,
+    // recomputing every cell from `initial` rather than keeping each generation around
+    fn replay(&self, generations: &[usize]) -> Option<usize> {
+        let mut states = self.initial.clone();
+        let mut generation = 0;
+        let mut scratch = Vec::new();
+        for target in generations.iter().cloned() {
+            while generation < target {
+                states = (0..states.len())
+                    .map(|cell| {
+                        scratch.clear();
+                        scratch.extend(self.rule.neighbours(cell).iter().map(|n| states[*n]));
+                        self.rule.next(cell, states[cell], &scratch)
+                    })
+                    .collect();
+                generation += 1;
+            }
+
+            if states == self.current {
+                return Some(target);
+            }
+        }
+
+        None
+    }
+
+    // steps until a state repeats, or gives up after `max_generations`
+    pub fn run(&mut self, max_generations: usize) -> Option<Cycle> {
+        for _ in 0..max_generations {
+            if self.cycle.is_some() {
+                break;
+            }
+
+            self.step();
+        }
+
+        self.cycle
+    }
+
+    // steps until no cell can change, returning that generation; `None` once the states
+    // start repeating without settling, as an oscillator does
+    pub fn run_until_stable(&mut self) -> Option<usize> {
+        loop {
+            if self.is_stable() {
+                return Some(self.generation);
+            }
+
+            if self.cycle.is_some() {
+                return None;
+            }
+
+            self.step();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // conway's life on a wrapping `size`x`size` board
+    struct Life {
+        neighbours: Vec<Vec<usize>>,
+    }
+
+    impl Life {
+        fn new(size: usize) -> Life {
+            let neighbours = (0..size * size)
+                .map(|cell| {
+                    let (row, column) = (cell / size, cell % size);
+                    let mut result = Vec::new();
+                    for dr in [size - 1, 0, 1].iter() {
+                        for dc in [size - 1, 0, 1].iter() {
+                            if *dr != 0 || *dc != 0 {
+                                result.push((row + dr) % size * size + (column + dc) % size);
+                            }
+                        }
+                    }
+                    result
+                })
+                .collect();
+
+            Life { neighbours }
+        }
+    }
+
+    impl Rule for Life {
+        type State = bool;
+
+        fn neighbours(&self, cell: usize) -> &[usize] {
+            &self.neighbours[cell]
+        }
+
+        fn next(&self, _cell: usize, alive: bool, neighbours: &[bool]) -> bool {
+            let count = neighbours.iter().filter(|n| **n).count();
+            count == 3 || (alive && count == 2)
+        }
+    }
+
+    fn board(size: usize, alive: &[(usize, usize)]) -> Vec<bool> {
+        let mut cells = vec![false; size * size];
+        for (row, column) in alive {
+            cells[row * size + column] = true;
+        }
+        cells
+    }
+
+    #[test]
+    fn still_life() {
+        let mut automaton = Automaton::new(Life::new(6), board(6, &[(1, 1), (1, 2), (2, 1), (2, 2)]));
+
+        assert_eq!(automaton.step(), 0);
+        assert!(automaton.is_stable());
+        assert_eq!(automaton.cycle(), Some(Cycle { start: 0, period: 1 }));
+        assert_eq!(automaton.run(10), Some(Cycle { start: 0, period: 1 }));
+
+        let mut automaton = Automaton::new(Life::new(6), board(6, &[(1, 1), (1, 2), (2, 1)]));
+        assert_eq!(automaton.run_until_stable(), Some(2));
+        assert_eq!(automaton.states(), &board(6, &[(1, 1), (1, 2), (2, 1), (2, 2)])[..]);
+    }
+
+    #[test]
+    fn blinker() {
+        let start = board(5, &[(2, 1), (2, 2), (2, 3)]);
+        let mut automaton = Automaton::new(Life::new(5), start.clone());
+
+        assert_eq!(automaton.step(), 4);
+        assert_eq!(automaton.states(), &board(5, &[(1, 2), (2, 2), (3, 2)])[..]);
+        assert_eq!(automaton.step(), 4);
+        assert_eq!(automaton.states(), &start[..]);
+
+        let mut automaton = Automaton::new(Life::new(5), start.clone());
+        assert_eq!(automaton.run(10), Some(Cycle { start: 0, period: 2 }));
+
+        let mut automaton = Automaton::new(Life::new(5), start);
+        assert_eq!(automaton.run_until_stable(), None);
+        assert_eq!(automaton.generation(), 2);
+    }
+
+    #[test]
+    fn glider_wraps_around() {
+        let mut automaton = Automaton::new(Life::new(8), board(8, &[(0, 1), (1, 2), (2, 0), (2, 1), (2, 2)]));
+
+        // one cell diagonally every 4 generations, back home after crossing the board
+        assert_eq!(automaton.run(100), Some(Cycle { start: 0, period: 32 }));
+        assert_eq!(automaton.states().iter().filter(|c| **c).count(), 5);
+    }
+
+    // a counter whose states all hash alike
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    struct Counter(u8);
+
+    impl Hash for Counter {
+        fn hash<H: Hasher>(&self, _state: &mut H) {}
+    }
+
+    struct Count;
+
+    impl Rule for Count {
+        type State = Counter;
+
+        fn neighbours(&self, _cell: usize) -> &[usize] {
+            &[]
+        }
+
+        fn next(&self, _cell: usize, state: Counter, _neighbours: &[Counter]) -> Counter {
+            Counter((state.0 + 1) % 5)
+        }
+    }
+
+    #[test]
+    fn hash_collisions_are_not_cycles() {
+        let mut automaton = Automaton::new(Count, vec![Counter(0), Counter(3)]);
+
+        assert_eq!(automaton.run(10), Some(Cycle { start: 0, period: 5 }));
+        assert_eq!(automaton.states(), &[Counter(0), Counter(3)][..]);
+    }
+
+    #[test]
+    fn frontier_matches_full_recompute() {
+        let size = 12;
+        let rule = Life::new(size);
+        let start = board(size, &[(1, 1), (1, 2), (2, 1), (5, 5), (5, 6), (5, 7), (6, 4), (6, 5), (6, 6), (9, 1), (10, 2), (11, 0)]);
+
+        let mut expected = start.clone();
+        let mut automaton = Automaton::new(Life::new(size), start);
+        for _ in 0..30 {
+            expected = (0..expected.len())
+                .map(|cell| {
+                    let neighbours = rule.neighbours(cell).iter().map(|n| expected[*n]).collect::<Vec<_>>();
+                    rule.next(cell, expected[cell], &neighbours)
+                })
+                .collect();
+
+            automaton.step();
+            assert_eq!(automaton.states(), &expected[..]);
+        }
+    }
+}
@@ -0,0 +1,232 @@
+use std::cmp::Ordering;
+use std::fmt;
+use std::ops::{ Add, AddAssign };
+use std::str::FromStr;
+
+// little endian base 2^32 limbs, never with trailing zero limbs
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Default)]
+pub struct BigUint {
+    limbs: Vec<u32>,
+}
+
+const DECIMAL_CHUNK: u32 = 1_000_000_000;
+const DECIMAL_CHUNK_DIGITS: usize = 9;
+
+impl BigUint {
+    pub fn zero() -> BigUint {
+        BigUint::default()
+    }
+
+    pub fn one() -> BigUint {
+        BigUint::from(1u64)
+    }
+
+    pub fn is_zero(&self) -> bool {
+        self.limbs.is_empty()
+    }
+
+    pub fn bits(&self) -> usize {
+        match self.limbs.last() {
+            Some(top) => self.limbs.len() * 32 - top.leading_zeros() as usize,
+            None => 0,
+        }
+    }
+
+    pub fn to_u64(&self) -> Option<u64> {
+        match self.limbs[..] {
+            [] => Some(0),
+            [lo] => Some(lo as u64),
+            [lo, hi] => Some(((hi as u64) << 32) | lo as u64),
+            _ => None,
+        }
+    }
+
+    fn normalize(&mut self) {
+        while self.limbs.last() == Some(&0) {
+            self.limbs.pop();
+        }
+    }
+
+    pub fn mul_small(&mut self, factor: u32) {
+        let mut carry = 0u64;
+        for limb in self.limbs.iter_mut() {
+            let v = *limb as u64 * factor as u64 + carry;
+            *limb = v as u32;
+            carry = v >> 32;
+        }
+
+        if carry > 0 {
+            self.limbs.push(carry as u32);
+        }
+        self.normalize();
+    }
+
+    pub fn add_small(&mut self, value: u32) {
+        *self += &BigUint::from(value as u64);
+    }
+
+    // divides in place, returning the remainder
+    pub fn div_rem_small(&mut self, divisor: u32) -> u32 {
+        assert!(divisor != 0, "Division by zero");
+
+        let mut rem = 0u64;
+        for limb in self.limbs.iter_mut().rev() {
+            let v = (rem << 32) | *limb as u64;
+            *limb = (v / divisor as u64) as u32;
+            rem = v % divisor as u64;
+        }
+
+        self.normalize();
+        rem as u32
+    }
+}
+
+impl From<u64> for BigUint {
+    fn from(value: u64) -> BigUint {
+        let mut result = BigUint {
+            limbs: vec![value as u32, (value >> 32) as u32],
+        };
+        result.normalize();
+        result
+    }
+}
+
+impl<'a> AddAssign<&'a BigUint> for BigUint {
+    fn add_assign(&mut self, rhs: &'a BigUint) {
+        if self.limbs.len() < rhs.limbs.len() {
+            self.limbs.resize(rhs.limbs.len(), 0);
+        }
+
+        let mut carry = 0u64;
+        for (i, limb) in self.limbs.iter_mut().enumerate() {
+            let v = *limb as u64 + *rhs.limbs.get(i).unwrap_or(&0) as u64 + carry;
+            *limb = v as u32;
+            carry = v >> 32;
+        }
+
+        if carry > 0 {
+            self.limbs.push(carry as u32);
+        }
+    }
+}
+
+impl<'b> Add<&'b BigUint> for &BigUint {
+    type Output = BigUint;
+
+    fn add(self, rhs: &'b BigUint) -> BigUint {
+        let mut result = self.clone();
+        result += rhs;
+        result
+    }
+}
+
+impl Add for BigUint {
+    type Output = BigUint;
+
+    fn add(mut self, rhs: BigUint) -> BigUint {
+        self += &rhs;
+        self
+    }
+}
+
+impl Ord for BigUint {
+    fn cmp(&self, other: &BigUint) -> Ordering {
+        self.limbs.len()
+            .cmp(&other.limbs.len())
+            .then_with(|| self.limbs.iter().rev().cmp(other.limbs.iter().rev()))
+    }
+}
+
+impl PartialOrd for BigUint {
+    fn partial_cmp(&self, other: &BigUint) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl fmt::Display for BigUint {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.is_zero() {
+            return f.pad_integral(true, "", "0");
+        }
+
+        let mut chunks = Vec::new();
+        let mut rest = self.clone();
+        while !rest.is_zero() {
+            chunks.push(rest.div_rem_small(DECIMAL_CHUNK));
+        }
+
+        let mut digits = chunks.pop().unwrap().to_string();
+        for chunk in chunks.iter().rev() {
+            digits += &format!("{:0width$}", chunk, width = DECIMAL_CHUNK_DIGITS);
+        }
+
+        f.pad_integral(true, "", &digits)
+    }
+}
+
+impl FromStr for BigUint {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<BigUint, String> {
+        if s.is_empty() {
+            return Err("Empty number".to_owned());
+        }
+
+        let mut result = BigUint::zero();
+        for c in s.chars() {
+            let digit = c.to_digit(10).ok_or_else(|| format!("Invalid digit={:?}", c))?;
+            result.mul_small(10);
+            result.add_small(digit);
+        }
+
+        Ok(result)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn small_values() {
+        assert_eq!(BigUint::zero().to_string(), "0");
+        assert_eq!(BigUint::from(19208).to_string(), "19208");
+        assert_eq!(BigUint::from(u64::MAX).to_u64(), Some(u64::MAX));
+        assert_eq!(format!("{:>6}", BigUint::from(42)), "    42");
+        assert!(BigUint::from(0).is_zero());
+    }
+
+    #[test]
+    fn carries_past_u64() {
+        let max = BigUint::from(u64::MAX);
+        let sum = &max + &BigUint::one();
+
+        assert_eq!(sum.to_string(), "18446744073709551616");
+        assert_eq!(sum.to_u64(), None);
+        assert_eq!(sum.bits(), 65);
+        assert!(sum > max);
+    }
+
+    #[test]
+    fn fibonacci() {
+        let (mut a, mut b) = (BigUint::zero(), BigUint::one());
+        for _ in 0..200 {
+            let next = &a + &b;
+            a = b;
+            b = next;
+        }
+
+        assert_eq!(a.to_string(), "280571172992510140037611932413038677189525");
+        assert_eq!("280571172992510140037611932413038677189525".parse(), Ok(a));
+    }
+
+    #[test]
+    fn parsing_and_division() {
+        let mut n = "1000000000000000000000007".parse::<BigUint>().unwrap();
+        assert_eq!(n.div_rem_small(10), 7);
+        assert_eq!(n.to_string(), "100000000000000000000000");
+
+        assert_eq!("12a".parse::<BigUint>(), Err("Invalid digit='a'".to_owned()));
+        assert!("".parse::<BigUint>().is_err());
+    }
+}
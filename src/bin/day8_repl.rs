@@ -0,0 +1,36 @@
+extern crate advent_of_code_2020;
+
+use advent_of_code_2020::day8::{ parse_program, repl, Vm };
+use std::env;
+use std::fs;
+use std::io;
+use std::process;
+
+fn main() {
+    let path = env::args().nth(1).unwrap_or_else(|| "input/2020/day8.txt".to_owned());
+
+    let source = match fs::read_to_string(&path) {
+        Ok(source) => source,
+        Err(e) => {
+            eprintln!("Failed to read {}: {}", path, e);
+            process::exit(1);
+        },
+    };
+
+    let program = match parse_program(&source) {
+        Ok(program) => program,
+        Err(e) => {
+            eprintln!("Failed to parse {}: {}", path, e);
+            process::exit(1);
+        },
+    };
+
+    let mut vm = Vm::new(program);
+    println!("Loaded {} instructions from {}, type `help` for commands", vm.program().len(), path);
+
+    let stdin = io::stdin();
+    if let Err(e) = repl(&mut vm, stdin.lock(), io::stdout()) {
+        eprintln!("{}", e);
+        process::exit(1);
+    }
+}
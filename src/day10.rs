@@ -1,4 +1,6 @@
-use std::collections::HashMap;
+use biguint::BigUint;
+use std::collections::{ HashMap, HashSet, VecDeque };
+use std::fmt;
 use std::iter;
 
 #[aoc_generator(day10)]
@@ -8,36 +10,41 @@ pub fn input_generator(input: &str) -> Vec<u32> {
         .collect()
 }
 
-fn sort_adaptors(adaptors: &[u32]) -> Vec<u32> {
+pub fn sort_adaptors(adaptors: &[u32]) -> Vec<u32> {
     let mut adaptors = adaptors.to_vec();
     adaptors.sort_unstable();
 
     adaptors
 }
 
-fn compute_jolt_diffs(adaptors: &[u32]) -> (usize, usize) {
-    let adaptors = sort_adaptors(adaptors);
+const DEFAULT_MAX_GAP: u32 = 3;
 
-    let mut one_diffs = 0;
-    let mut three_diffs = 0;
+// the device is rated `max_gap` above the highest adaptor
+fn device_jolts(adaptors: &[u32], max_gap: u32) -> u32 {
+    adaptors.iter().max().unwrap_or(&0) + max_gap
+}
+
+// how many steps of each size the full chain takes, indexed by gap
+fn compute_jolt_diffs(adaptors: &[u32], max_gap: u32) -> Vec<usize> {
+    let adaptors = sort_adaptors(adaptors);
 
     let left_iter = iter::once(&0).chain(adaptors.iter());
-    let console_jolts = adaptors[adaptors.len() - 1] + 3;
+    let console_jolts = device_jolts(&adaptors, max_gap);
     let right_iter = adaptors.iter().chain(iter::once(&console_jolts));
 
+    let mut diffs = vec![0; max_gap as usize + 1];
     for (left, right) in left_iter.zip(right_iter) {
-        let diff = right - left;
-        match diff {
-            1 => one_diffs += 1,
-            3 => three_diffs += 1,
-            _ => {},
-        };
+        let diff = (right - left) as usize;
+        if diff >= diffs.len() {
+            diffs.resize(diff + 1, 0);
+        }
+        diffs[diff] += 1;
     }
 
-    (one_diffs, three_diffs)
+    diffs
 }
 
-fn compute_traversal_graph(adaptors: &[u32], console_jolts: &u32) -> HashMap<u32, Vec<u32>> {
+pub fn compute_traversal_graph(adaptors: &[u32], console_jolts: &u32, max_gap: u32) -> HashMap<u32, Vec<u32>> {
     // pad with end points
     let adaptors = iter::once(0)
         .chain(adaptors.iter().cloned())
@@ -45,13 +52,12 @@ fn compute_traversal_graph(adaptors: &[u32], console_jolts: &u32) -> HashMap<u32
         .collect::<Vec<_>>();
 
     let mut result = HashMap::<u32, Vec<u32>>::with_capacity(adaptors.len());
-    
+
     for (i, current) in adaptors.iter().take(adaptors.len() - 1).enumerate() {
         let options = adaptors[i+1..].iter()
-            .take(3)
-            .filter(|n| *n - current <= 3)
+            .take_while(|n| *n - current <= max_gap)
             .cloned()
-            .collect::<Vec<_>>();        
+            .collect::<Vec<_>>();
 
         result.insert(*current, options);
     }
@@ -59,48 +65,188 @@ fn compute_traversal_graph(adaptors: &[u32], console_jolts: &u32) -> HashMap<u32
     result
 }
 
-fn count_possibilities(graph: &HashMap<u32, Vec<u32>>, terminal: &u32) -> usize { 
-    let mut memory = HashMap::with_capacity(graph.len());
+// arrangements from the outlet to the device, counting forward over the sorted adaptors
+fn count_arrangements(adaptors: &[u32], max_gap: u32) -> BigUint {
+    let console_jolts = device_jolts(adaptors, max_gap);
+    let joltages = iter::once(0)
+        .chain(adaptors.iter().cloned())
+        .chain(iter::once(console_jolts))
+        .collect::<Vec<_>>();
+
+    let mut ways = vec![BigUint::zero(); joltages.len()];
+    ways[0] = BigUint::one();
+
+    for (i, current) in joltages.iter().enumerate().skip(1) {
+        let mut total = BigUint::zero();
+        for (j, previous) in joltages[..i].iter().enumerate().rev() {
+            if current - previous > max_gap {
+                break;
+            }
+            total += &ways[j];
+        }
+        ways[i] = total;
+    }
+
+    ways.pop().unwrap()
+}
+
+// every chain from the outlet to the device, depth first in increasing joltage order
+pub struct Chains<'a> {
+    graph: &'a HashMap<u32, Vec<u32>>,
+    console_jolts: u32,
+    // each node on the current path and the index of its next option to try
+    stack: Vec<(u32, usize)>,
+}
+
+impl<'a> Iterator for Chains<'a> {
+    // the adaptors used, without the outlet and device
+    type Item = Vec<u32>;
+
+    fn next(&mut self) -> Option<Vec<u32>> {
+        while let Some((node, next)) = self.stack.last_mut() {
+            let option = self.graph.get(node).and_then(|options| options.get(*next)).cloned();
+            *next += 1;
+
+            match option {
+                None => {
+                    self.stack.pop();
+                },
+                Some(jolts) if jolts == self.console_jolts => {
+                    return Some(self.stack.iter().skip(1).map(|(node, _)| *node).collect());
+                },
+                Some(jolts) => self.stack.push((jolts, 0)),
+            }
+        }
+
+        None
+    }
+}
+
+pub fn chains(graph: &HashMap<u32, Vec<u32>>, console_jolts: u32) -> Chains<'_> {
+    Chains {
+        graph,
+        console_jolts,
+        stack: vec![(0, 0)],
+    }
+}
+
+// the chain with the fewest adaptors, preferring lower joltages on ties
+pub fn shortest_chain(graph: &HashMap<u32, Vec<u32>>, console_jolts: u32) -> Option<Vec<u32>> {
+    let mut parents = HashMap::new();
+    let mut queue = VecDeque::new();
+    queue.push_back(0);
+
+    while let Some(node) = queue.pop_front() {
+        if node == console_jolts {
+            let mut chain = Vec::new();
+            let mut current = parents[&node];
+            while current != 0 {
+                chain.push(current);
+                current = parents[&current];
+            }
+            chain.reverse();
+            return Some(chain);
+        }
+
+        for option in graph.get(&node).into_iter().flatten() {
+            if !parents.contains_key(option) {
+                parents.insert(*option, node);
+                queue.push_back(*option);
+            }
+        }
+    }
+
+    None
+}
+
+// adaptors that appear in every chain, `None` when there is no chain at all
+pub fn mandatory_adaptors(graph: &HashMap<u32, Vec<u32>>, console_jolts: u32) -> Option<Vec<u32>> {
+    let mut nodes = graph.keys().cloned().collect::<Vec<_>>();
+    nodes.sort_unstable();
+
+    // nodes that can still reach the device, found walking backwards
+    let mut useful = HashSet::new();
+    useful.insert(console_jolts);
+    for node in nodes.iter().rev() {
+        if graph[node].iter().any(|option| useful.contains(option)) {
+            useful.insert(*node);
+        }
+    }
+
+    if !useful.contains(&0) {
+        return None;
+    }
 
-    fn count_possibilities_(graph: &HashMap<u32, Vec<u32>>, memory: &mut HashMap<u32, usize>, terminal: &u32, current: &u32) -> usize {
-        if *current == *terminal {
-            return 1;
+    // the graph only steps upwards, so an adaptor is mandatory unless some used step jumps over it
+    let mut reach = 0;
+    let mut mandatory = Vec::new();
+    for node in nodes.iter().filter(|node| useful.contains(node)) {
+        if *node != 0 && reach <= *node {
+            mandatory.push(*node);
         }
 
-        let possibilities = &graph[current];
-        possibilities.iter()
-            .map(|p| {
-                if *p == *terminal {
-                    1
-                } 
-                else if let Some(memoized) = memory.get(p) {
-                    *memoized
-                }
-                else {
-                    let v = count_possibilities_(&graph, memory, terminal, p); 
-                    memory.insert(*p, v); // memoize to avoid extra traversals
-                    v
-                }
-            }) 
-            .sum()
+        let furthest = graph[node].iter()
+            .filter(|option| useful.contains(option))
+            .max()
+            .unwrap();
+        reach = std::cmp::max(reach, *furthest);
     }
 
-    count_possibilities_(&graph, &mut memory, &terminal, &0)
+    Some(mandatory)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Gap {
+    pub below: u32,
+    pub above: u32,
+    pub max_gap: u32,
+}
+
+impl Gap {
+    pub fn size(&self) -> u32 {
+        self.above - self.below
+    }
+}
+
+impl fmt::Display for Gap {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.below == 0 {
+            write!(f, "Lowest adaptor={} is more than {} jolts above the outlet", self.above, self.max_gap)
+        }
+        else {
+            write!(f, "Nothing bridges {} to {}, a gap of {} with at most {} allowed", self.below, self.above, self.size(), self.max_gap)
+        }
+    }
+}
+
+// the steps between sorted adaptors that are too wide to ever take, empty when chains exist
+pub fn unusable_gaps(adaptors: &[u32], max_gap: u32) -> Vec<Gap> {
+    let adaptors = sort_adaptors(adaptors);
+    let console_jolts = device_jolts(&adaptors, max_gap);
+    let graph = compute_traversal_graph(&adaptors, &console_jolts, max_gap);
+
+    let mut nodes = graph.keys().cloned().collect::<Vec<_>>();
+    nodes.sort_unstable();
+
+    nodes.iter()
+        .filter(|node| graph[node].is_empty())
+        .map(|node| Gap {
+            below: *node,
+            above: *adaptors.iter().find(|a| *a > node).unwrap_or(&console_jolts),
+            max_gap,
+        })
+        .collect()
 }
 
 #[aoc(day10, part1)]
 pub fn solve_part1(adaptors: &[u32]) -> usize {
-    let (one_diffs, three_diffs) = compute_jolt_diffs(&adaptors);
-    one_diffs * three_diffs
+    let diffs = compute_jolt_diffs(adaptors, DEFAULT_MAX_GAP);
+    diffs[1] * diffs[3]
 }
 
 #[aoc(day10, part2)]
-pub fn solve_part2(adaptors: &[u32]) -> usize {
-    let adaptors = sort_adaptors(adaptors);
-    let console_jolts = adaptors[adaptors.len() - 1] + 3;
-
-    let graph = compute_traversal_graph(&adaptors, &console_jolts);
-    count_possibilities(&graph, &console_jolts)
+pub fn solve_part2(adaptors: &[u32]) -> BigUint {
+    count_arrangements(&sort_adaptors(adaptors), DEFAULT_MAX_GAP)
 }
 
 #[cfg(test)]
@@ -162,12 +308,12 @@ mod tests {
         let adaptors = sort_adaptors(&input);
         let console_jolts = adaptors[adaptors.len() - 1] + 3;
     
-        let graph = compute_traversal_graph(&adaptors, &console_jolts);
+        let graph = compute_traversal_graph(&adaptors, &console_jolts, 3);
         println!("graph = {:?}", graph);
         assert_eq!(graph.len(), input.len() + 1);
 
-        let count = count_possibilities(&graph, &console_jolts);
-        assert_eq!(count, 8);
+        let count = count_arrangements(&adaptors, 3);
+        assert_eq!(count, BigUint::from(8));
     }
 
     #[test]
@@ -177,31 +323,102 @@ mod tests {
         let adaptors = sort_adaptors(&input);
         let console_jolts = adaptors[adaptors.len() - 1] + 3;
     
-        let graph = compute_traversal_graph(&adaptors, &console_jolts);
+        let graph = compute_traversal_graph(&adaptors, &console_jolts, 3);
         println!("graph = {:?}", graph);
         assert_eq!(graph.len(), input.len() + 1);
 
-        let count = count_possibilities(&graph, &console_jolts);
-        assert_eq!(count, 19208);
+        let count = count_arrangements(&adaptors, 3);
+        assert_eq!(count, BigUint::from(19208));
     }
 
     #[test]
     fn given_input1_part1() {
         let input = GIVEN_INPUT_1;
 
-        let (one_diffs, three_diffs) = compute_jolt_diffs(&input);
+        let diffs = compute_jolt_diffs(&input, 3);
 
-        assert_eq!(7, one_diffs);
-        assert_eq!(5, three_diffs);
+        assert_eq!(7, diffs[1]);
+        assert_eq!(5, diffs[3]);
     }
 
     #[test]
     fn given_input2_part1() {
         let input = GIVEN_INPUT_2;
 
-        let (one_diffs, three_diffs) = compute_jolt_diffs(&input);
+        let diffs = compute_jolt_diffs(&input, 3);
+
+        assert_eq!(22, diffs[1]);
+        assert_eq!(10, diffs[3]);
+    }
 
-        assert_eq!(22, one_diffs);
-        assert_eq!(10, three_diffs);
+    #[test]
+    fn arrangements_past_u64() {
+        // every adaptor one jolt apart: tribonacci growth
+        let adaptors = (1..=100).collect::<Vec<u32>>();
+
+        assert_eq!(
+            count_arrangements(&adaptors, 3).to_string(),
+            "180396380815100901214157639");
+        assert_eq!(solve_part2(&adaptors), count_arrangements(&adaptors, 3));
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn configurable_gap() {
+        let adaptors = sort_adaptors(&GIVEN_INPUT_1);
+
+        assert_eq!(compute_jolt_diffs(&adaptors, 4), vec![0, 7, 0, 4, 1]);
+        assert_eq!(count_arrangements(&adaptors, 1), BigUint::zero());
+        assert_eq!(count_arrangements(&adaptors, 4), BigUint::from(170));
+
+        let graph = compute_traversal_graph(&adaptors, &23, 4);
+        assert_eq!(graph[&4], vec![5, 6, 7]);
+        assert_eq!(graph[&12], vec![15, 16]);
+    }
+
+    fn graph_for(adaptors: &[u32], max_gap: u32) -> (HashMap<u32, Vec<u32>>, u32) {
+        let adaptors = sort_adaptors(adaptors);
+        let console_jolts = device_jolts(&adaptors, max_gap);
+        (compute_traversal_graph(&adaptors, &console_jolts, max_gap), console_jolts)
+    }
+
+    #[test]
+    fn enumerate_chains() {
+        let (graph, console_jolts) = graph_for(&GIVEN_INPUT_1, 3);
+        let all = chains(&graph, console_jolts).collect::<Vec<_>>();
+
+        assert_eq!(all.len(), 8);
+        assert_eq!(all[0], vec![1, 4, 5, 6, 7, 10, 11, 12, 15, 16, 19]);
+        assert_eq!(all[7], vec![1, 4, 7, 10, 12, 15, 16, 19]);
+
+        let (graph, console_jolts) = graph_for(&GIVEN_INPUT_2, 3);
+        assert_eq!(chains(&graph, console_jolts).take(5).count(), 5);
+        assert_eq!(chains(&graph, console_jolts).count(), 19208);
+    }
+
+    #[test]
+    fn shortest_and_mandatory() {
+        let (graph, console_jolts) = graph_for(&GIVEN_INPUT_1, 3);
+
+        assert_eq!(shortest_chain(&graph, console_jolts), Some(vec![1, 4, 7, 10, 12, 15, 16, 19]));
+        assert_eq!(mandatory_adaptors(&graph, console_jolts), Some(vec![1, 4, 7, 10, 12, 15, 16, 19]));
+
+        let (graph, console_jolts) = graph_for(&[1, 5], 3);
+        assert_eq!(shortest_chain(&graph, console_jolts), None);
+        assert_eq!(mandatory_adaptors(&graph, console_jolts), None);
+        assert_eq!(chains(&graph, console_jolts).count(), 0);
+    }
+
+    #[test]
+    fn explain_gaps() {
+        assert_eq!(unusable_gaps(&GIVEN_INPUT_1, 3), vec![]);
+
+        let gaps = unusable_gaps(&[5, 6, 10, 11], 3);
+        assert_eq!(
+            gaps,
+            vec![Gap { below: 0, above: 5, max_gap: 3 }, Gap { below: 6, above: 10, max_gap: 3 }]);
+        assert_eq!(gaps[0].to_string(), "Lowest adaptor=5 is more than 3 jolts above the outlet");
+        assert_eq!(gaps[1].to_string(), "Nothing bridges 6 to 10, a gap of 4 with at most 3 allowed");
+
+        assert_eq!(unusable_gaps(&[5, 6, 10, 11], 5), vec![]);
+    }
+}
@@ -1,16 +1,23 @@
-#[derive(Clone, PartialEq, Debug)]
+use automaton::{ Automaton, Rule };
+use bit_vec::BitVec;
+use image::{ self, ImageFormat };
+use std::fs;
+use std::io::{ self, Write };
+use std::path::{ Path, PathBuf };
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
 pub enum Space {
     Floor,
     Occupied,
     Empty,
 }
 
-impl Space {
-    fn is_unoccupied(&self) -> bool {
-        match self {
-            Space::Floor | Space::Empty => true,
-            Space::Occupied => false,
-        }
+fn parse_space(c: char) -> Option<Space> {
+    match c {
+        'L' => Some(Space::Empty),
+        '#' => Some(Space::Occupied),
+        '.' => Some(Space::Floor),
+        _ => None,
     }
 }
 
@@ -19,169 +26,496 @@ pub fn input_generator(input: &str) -> Vec<Vec<Space>> {
     input.lines()
         .map(|line| line.chars()
             .filter(|c| !c.is_whitespace())
-            .map(|c| match c {
-                'L' => Space::Empty,
-                '#' => Space::Occupied,
-                '.' => Space::Floor,
-                _ => unreachable!("Unsupported c = {}", c),
-            }).collect::<Vec<_>>()
+            .map(|c| parse_space(c).unwrap_or_else(|| unreachable!("Unsupported c = {}", c)))
+            .collect::<Vec<_>>()
         )
         .collect()
 }
 
-fn count_occupied(layout: &[Vec<Space>], i: usize, j: usize) -> usize {
-    // [i - 1, j - 1][i - 1, j][i - 1, j + 1]
-    // [    i, j - 1][    i, j][    i, j + 1]
-    // [i + 1, j - 1][i + 1, j][i + 1, j + 1]
-    
-    let mut count = 0;
+// a hex map draws the rows that sit half a seat to the right indented further than the
+// others, e.g. "LLL\n LLL\nLLL" for `HexOffset::OddRows`
+pub fn parse_hex(input: &str) -> Result<(Vec<Vec<Space>>, HexOffset), String> {
+    let lines = input.lines().collect::<Vec<_>>();
+    let indents = lines.iter()
+        .map(|line| line.len() - line.trim_start().len())
+        .collect::<Vec<_>>();
 
-    fn check_columns(row: &[Space], idx: usize) -> usize {
-        let mut count = 0;
-        if idx > 0 && !row[idx - 1].is_unoccupied() { 
-            count += 1 
-        } 
+    let offset = match (indents.first(), indents.get(1)) {
+        (Some(even), Some(odd)) if odd > even => HexOffset::OddRows,
+        (Some(even), Some(odd)) if even > odd => HexOffset::EvenRows,
+        (Some(_), Some(_)) => return Err("Rows 0 and 1 have the same indentation".to_string()),
+        // a single row leans neither way
+        _ => HexOffset::OddRows,
+    };
+
+    let mut layout: Vec<Vec<Space>> = Vec::with_capacity(lines.len());
+    for (row, line) in lines.iter().enumerate() {
+        if indents[row] != indents[row % 2] {
+            return Err(format!("Row={} is indented by {} instead of {}", row, indents[row], indents[row % 2]));
+        }
 
-        if idx < row.len() - 1 && !row[idx + 1].is_unoccupied() { 
-            count += 1;
-        } 
+        let seats = line.chars()
+            .filter(|c| !c.is_whitespace())
+            .map(|c| parse_space(c).ok_or_else(|| format!("Row={} has unsupported c={:?}", row, c)))
+            .collect::<Result<Vec<_>, _>>()?;
 
-        count
+        if !layout.is_empty() && seats.len() != layout[0].len() {
+            return Err(format!("Row={} has {} seats instead of {}", row, seats.len(), layout[0].len()));
+        }
+        layout.push(seats);
     }
 
-    if i > 0 {
-        let row = &layout[i - 1];
-        count += check_columns(row, j);
-        if !row[j].is_unoccupied() {
-            count += 1;
+    Ok((layout, offset))
+}
+
+const DIRECTIONS: [(i64, i64); 8] = [(-1, -1), (-1, 0), (-1, 1), (0, -1), (0, 1), (1, -1), (1, 0), (1, 1)];
+
+// axial (column, row) steps; see `Topology::Hex`
+const HEX_DIRECTIONS: [(i64, i64); 6] = [(1, 0), (1, -1), (0, -1), (-1, 0), (-1, 1), (0, 1)];
+
+// which rows of a hex map sit half a seat to the right of the others
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HexOffset {
+    OddRows,
+    EvenRows,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Topology {
+    Bounded,
+    // rays leaving one edge come back in on the opposite one
+    Toroidal,
+    // alternate rows are shifted by half a seat, so every seat has six neighbours
+    Hex(HexOffset),
+}
+
+impl Topology {
+    fn directions(&self) -> &'static [(i64, i64)] {
+        match self {
+            Topology::Bounded | Topology::Toroidal => &DIRECTIONS,
+            Topology::Hex(_) => &HEX_DIRECTIONS,
         }
     }
 
-    let row = &layout[i];
-    count += check_columns(row, j);
+    // the cell `steps` along `direction` from `(i, j)`, if it is on the map
+    fn walk(&self, rows: usize, columns: usize, (i, j): (usize, usize), direction: (i64, i64), steps: usize) -> Option<(usize, usize)> {
+        let (rows, columns, steps) = (rows as i64, columns as i64, steps as i64);
+        let (i, j) = (i as i64, j as i64);
+
+        let (ni, nj) = match self {
+            Topology::Bounded => (i + direction.0 * steps, j + direction.1 * steps),
+            Topology::Toroidal => (
+                (i + direction.0 * steps).rem_euclid(rows),
+                (j + direction.1 * steps).rem_euclid(columns)),
+            Topology::Hex(offset) => {
+                // how far a row's first seat is from the axial q = 0 line
+                let lean = |row: i64| match offset {
+                    HexOffset::OddRows => row.div_euclid(2),
+                    HexOffset::EvenRows => (row + 1).div_euclid(2),
+                };
+
+                let q = j - lean(i) + direction.0 * steps;
+                let r = i + direction.1 * steps;
+                (r, q + lean(r))
+            },
+        };
+
+        if ni >= 0 && nj >= 0 && ni < rows && nj < columns { Some((ni as usize, nj as usize)) } else { None }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SightRange {
+    Unbounded,
+    // only seats at most this many cells away are seen
+    Cells(usize),
+}
 
-    if i < layout.len() - 1 {
-        let row = &layout[i + 1];
-        count += check_columns(row, j);
-        if !row[j].is_unoccupied() {
-            count += 1;
+impl SightRange {
+    fn reaches(&self, distance: usize) -> bool {
+        match self {
+            SightRange::Unbounded => true,
+            SightRange::Cells(n) => distance <= *n,
         }
     }
+}
 
-    count
+// for every cell, the first seat visible in each direction, computed once per layout;
+// floor cells see nothing, since they never change
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SightLines {
+    columns: usize,
+    // seats visible from cell `c` are `seats[offsets[c]..offsets[c + 1]]`
+    offsets: Vec<usize>,
+    seats: Vec<usize>,
 }
 
-fn count_occupied_sight(layout: &[Vec<Space>], i: usize, j: usize) -> usize {
-    // [i - 1, j - 1][i - 1, j][i - 1, j + 1]
-    // [    i, j - 1][    i, j][    i, j + 1]
-    // [i + 1, j - 1][i + 1, j][i + 1, j + 1]
-    
-    let mut count = 0;
+impl SightLines {
+    pub fn new(layout: &[Vec<Space>], range: SightRange) -> SightLines {
+        let rows = layout.len();
+        let columns = layout.first().map_or(0, |row| row.len());
+        let is_seat = |i: usize, j: usize| layout[i][j] != Space::Floor;
+
+        let mut visible = vec![Vec::new(); rows * columns];
+        // per direction: the first seat from each cell and how far away it is
+        let mut first = vec![None; rows * columns];
+
+        for (di, dj) in DIRECTIONS.iter() {
+            // walk against the direction so the cell one step along it is already done
+            let row_order = if *di > 0 { (0..rows).rev().collect::<Vec<_>>() } else { (0..rows).collect() };
+            let column_order = if *dj > 0 { (0..columns).rev().collect::<Vec<_>>() } else { (0..columns).collect() };
+
+            for i in row_order.iter().cloned() {
+                for j in column_order.iter().cloned() {
+                    let (ni, nj) = (i as i64 + di, j as i64 + dj);
+                    let seen = if ni < 0 || nj < 0 || ni as usize >= rows || nj as usize >= columns {
+                        None
+                    }
+                    else {
+                        let (ni, nj) = (ni as usize, nj as usize);
+                        if is_seat(ni, nj) {
+                            Some((ni * columns + nj, 1))
+                        }
+                        else {
+                            first[ni * columns + nj].map(|(seat, distance)| (seat, distance + 1))
+                        }
+                    };
 
-    fn check_diag(layout: &[Vec<Space>], row_len: &usize, i: &usize, j: &usize, walk: (i32, i32)) -> bool {
-        let mut i = *i as i64 + walk.0 as i64;
-        let mut j = *j as i64 + walk.1 as i64;
+                    let seen = seen.filter(|(_, distance)| range.reaches(*distance));
+                    first[i * columns + j] = seen;
 
-        loop {
-            if i < 0 || j < 0 {
-                return false
+                    if let Some((seat, _)) = seen {
+                        if is_seat(i, j) {
+                            visible[i * columns + j].push(seat);
+                        }
+                    }
+                }
             }
+        }
 
-            let ui = i as usize;
-            let uj = j as usize;
+        SightLines::from_visible(columns, visible)
+    }
 
-            if ui >= layout.len() || uj >= *row_len {
-                return false
-            }
+    fn from_visible(columns: usize, visible: Vec<Vec<usize>>) -> SightLines {
+        let mut offsets = Vec::with_capacity(visible.len() + 1);
+        let mut seats = Vec::new();
+        offsets.push(0);
+        for cell in visible {
+            seats.extend(cell);
+            offsets.push(seats.len());
+        }
 
-            match &layout[i as usize][j as usize] {
-                Space::Occupied => return true,
-                Space::Empty => return false,
-                _ => {}
-            }
+        SightLines { columns, offsets, seats }
+    }
 
-            i += walk.0 as i64;
-            j += walk.1 as i64;
+    pub fn with_topology(layout: &[Vec<Space>], topology: Topology, range: SightRange) -> SightLines {
+        match topology {
+            Topology::Bounded => SightLines::new(layout, range),
+            Topology::Toroidal => SightLines::toroidal(layout, range),
+            Topology::Hex(_) => SightLines::hex(layout, topology, range),
         }
     }
 
-    let row_len = layout[0].len();
+    // on a torus each direction splits the cells into closed loops; one pass backwards round
+    // a loop finds the first seat ahead of every cell on it, so no ray is walked twice
+    fn toroidal(layout: &[Vec<Space>], range: SightRange) -> SightLines {
+        let rows = layout.len();
+        let columns = layout.first().map_or(0, |row| row.len());
+        let cells = layout.concat();
+
+        let mut visible = vec![Vec::new(); cells.len()];
+        for direction in DIRECTIONS.iter() {
+            let mut done = BitVec::from_elem(cells.len(), false);
+
+            for start in 0..cells.len() {
+                if done[start] {
+                    continue;
+                }
+
+                let mut ring = vec![start];
+                loop {
+                    let cell = ring[ring.len() - 1];
+                    let (ni, nj) = Topology::Toroidal.walk(rows, columns, (cell / columns, cell % columns), *direction, 1).unwrap();
+                    if ni * columns + nj == start {
+                        break;
+                    }
+                    ring.push(ni * columns + nj);
+                }
+
+                // positions `len..2 * len` go round a second time, so the first seats of
+                // the loop know about the last ones; `ahead` is the nearest seat after `k`
+                let len = ring.len();
+                let mut ahead = None;
+                for k in (0..2 * len).rev() {
+                    let cell = ring[k % len];
+                    if k < len && cells[cell] != Space::Floor {
+                        // a seat `len` ahead is the cell itself
+                        if let Some(seat) = ahead.filter(|seat| seat - k < len && range.reaches(seat - k)) {
+                            visible[cell].push(ring[seat % len]);
+                        }
+                    }
+                    done.set(cell, true);
 
-    for i_offset in -1..=1 {
-        for j_offset in -1..=1 {
-            if i_offset == 0 && j_offset == 0 {
-                continue;
+                    if cells[cell] != Space::Floor {
+                        ahead = Some(k);
+                    }
+                }
             }
+        }
+
+        SightLines::from_visible(columns, visible)
+    }
 
-            if check_diag(&layout, &row_len, &i, &j, (i_offset, j_offset)) {
-                count += 1;
+    // walks every ray; a hex ray never comes back onto the map once it leaves it
+    fn hex(layout: &[Vec<Space>], topology: Topology, range: SightRange) -> SightLines {
+        let rows = layout.len();
+        let columns = layout.first().map_or(0, |row| row.len());
+
+        let mut visible = vec![Vec::new(); rows * columns];
+        for (i, row) in layout.iter().enumerate() {
+            for (j, space) in row.iter().enumerate() {
+                if *space != Space::Floor {
+                    for direction in topology.directions() {
+                        let seat = (1..)
+                            .take_while(|steps| range.reaches(*steps))
+                            .map(|steps| topology.walk(rows, columns, (i, j), *direction, steps))
+                            .take_while(Option::is_some)
+                            .flatten()
+                            .find(|(ni, nj)| layout[*ni][*nj] != Space::Floor);
+
+                        visible[i * columns + j].extend(seat.map(|(ni, nj)| ni * columns + nj));
+                    }
+                }
             }
         }
+
+        SightLines::from_visible(columns, visible)
+    }
+
+    pub fn index(&self, i: usize, j: usize) -> usize {
+        i * self.columns + j
     }
 
-    count
+    pub fn visible(&self, cell: usize) -> &[usize] {
+        &self.seats[self.offsets[cell]..self.offsets[cell + 1]]
+    }
+
+    pub fn len(&self) -> usize {
+        self.offsets.len() - 1
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
 }
 
-type CountOccupiedFn = dyn Fn(&[Vec<Space>], usize, usize) -> usize;
-
-fn iterate_occupied(layout: &[Vec<Space>], count_occupied: &CountOccupiedFn, occupied_req: usize) -> (Vec<Vec<Space>>, bool){
-    let mut mutated = false;
-    let new_layout = layout.iter()
-        .enumerate()
-        .map(|(i, row)| {
-            row.iter()
-                .enumerate()
-                .map(|(j, seat)| match seat {
-                    Space::Occupied => {
-                        if count_occupied(layout, i, j) >= occupied_req {
-                            mutated = true;
-                            Space::Empty
-                        }
-                        else {
-                            seat.clone()
-                        }
-                    },
-                    Space::Empty => {
-                        if count_occupied(layout, i, j) == 0 {
-                            mutated = true;
-                            Space::Occupied
-                        }
-                        else {
-                            seat.clone()
-                        }
-                    },
-                    Space::Floor => Space::Floor,
-                })
-                .collect::<Vec<_>>()
-        })
-        .collect::<Vec<_>>();
+// `states` is the flattened layout the sight lines were built from
+pub fn count_occupied_sight(lines: &SightLines, states: &[Space], i: usize, j: usize) -> usize {
+    lines.visible(lines.index(i, j))
+        .iter()
+        .filter(|seat| states[**seat] == Space::Occupied)
+        .count()
+}
 
-    (new_layout, mutated)
+pub struct SeatingRule {
+    lines: SightLines,
+    // occupied neighbours it takes for someone to leave
+    tolerance: usize,
 }
 
-fn solve(layout: &[Vec<Space>], count_occupied: &CountOccupiedFn, occupied_req: usize) -> usize {
-    let mut mutated = true;
-    let mut new_layout = layout.to_vec();
+impl Rule for SeatingRule {
+    type State = Space;
 
-    while mutated {
-        let result = iterate_occupied(&new_layout, count_occupied, occupied_req);
-        mutated = result.1;
-        new_layout = result.0;
+    fn neighbours(&self, cell: usize) -> &[usize] {
+        self.lines.visible(cell)
     }
 
-    new_layout.into_iter()
-        .flat_map(|r| r.into_iter().filter(|s| *s == Space::Occupied))
-        .count()
+    fn next(&self, _cell: usize, state: Space, neighbours: &[Space]) -> Space {
+        let occupied = neighbours.iter().filter(|n| **n == Space::Occupied).count();
+        match state {
+            Space::Empty if occupied == 0 => Space::Occupied,
+            Space::Occupied if occupied >= self.tolerance => Space::Empty,
+            state => state,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RuleSet {
+    pub topology: Topology,
+    pub range: SightRange,
+    // occupied neighbours it takes for someone to leave
+    pub tolerance: usize,
+}
+
+pub const PART1_RULES: RuleSet = RuleSet {
+    topology: Topology::Bounded,
+    range: SightRange::Cells(1),
+    tolerance: 4,
+};
+
+pub const PART2_RULES: RuleSet = RuleSet {
+    topology: Topology::Bounded,
+    range: SightRange::Unbounded,
+    tolerance: 5,
+};
+
+impl Space {
+    pub fn symbol(&self) -> char {
+        match self {
+            Space::Floor => '.',
+            Space::Empty => 'L',
+            Space::Occupied => '#',
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Generation {
+    pub index: usize,
+    pub grid: Vec<Vec<Space>>,
+    pub occupied: usize,
+    pub became_occupied: usize,
+    pub vacated: usize,
+}
+
+impl Generation {
+    pub fn flipped(&self) -> usize {
+        self.became_occupied + self.vacated
+    }
+}
+
+// yields the starting layout, then every generation until the seating stops changing or
+// repeats an earlier arrangement
+pub struct Simulation {
+    automaton: Automaton<SeatingRule>,
+    columns: usize,
+    started: bool,
+}
+
+impl Simulation {
+    pub fn new(layout: &[Vec<Space>], rules: &RuleSet) -> Simulation {
+        let rule = SeatingRule {
+            lines: SightLines::with_topology(layout, rules.topology, rules.range),
+            tolerance: rules.tolerance,
+        };
+
+        Simulation {
+            automaton: Automaton::new(rule, layout.concat()),
+            columns: layout.first().map_or(0, |row| row.len()),
+            started: false,
+        }
+    }
+
+    fn generation(&self) -> Generation {
+        let states = self.automaton.states();
+        let became_occupied = self.automaton.changed()
+            .iter()
+            .filter(|cell| states[**cell] == Space::Occupied)
+            .count();
+
+        Generation {
+            index: self.automaton.generation(),
+            grid: states.chunks(std::cmp::max(self.columns, 1)).map(|row| row.to_vec()).collect(),
+            occupied: states.iter().filter(|s| **s == Space::Occupied).count(),
+            became_occupied,
+            vacated: self.automaton.changed().len() - became_occupied,
+        }
+    }
+}
+
+impl Iterator for Simulation {
+    type Item = Generation;
+
+    fn next(&mut self) -> Option<Generation> {
+        if !self.started {
+            self.started = true;
+            return Some(self.generation());
+        }
+
+        if self.automaton.cycle().is_some() || self.automaton.step() == 0 {
+            return None;
+        }
+
+        Some(self.generation())
+    }
+}
+
+// the occupied seats once seating settles, `None` if it oscillates forever instead
+pub fn solve(layout: &[Vec<Space>], rules: &RuleSet) -> Option<usize> {
+    let rule = SeatingRule {
+        lines: SightLines::with_topology(layout, rules.topology, rules.range),
+        tolerance: rules.tolerance,
+    };
+    let mut automaton = Automaton::new(rule, layout.concat());
+    let cycle = automaton.run(usize::MAX)?;
+
+    if cycle.period > 1 {
+        return None;
+    }
+
+    Some(automaton.states()
+        .iter()
+        .filter(|s| **s == Space::Occupied)
+        .count())
+}
+
+pub fn render_grid(grid: &[Vec<Space>]) -> String {
+    grid.iter()
+        .map(|row| row.iter().map(Space::symbol).collect::<String>() + "\n")
+        .collect()
+}
+
+pub fn write_text_frames<W: Write, I: IntoIterator<Item = Generation>>(generations: I, mut out: W) -> io::Result<()> {
+    for generation in generations {
+        writeln!(
+            out, "generation {}: {} occupied, {} flipped ({} in, {} out)",
+            generation.index, generation.occupied, generation.flipped(), generation.became_occupied, generation.vacated)?;
+        writeln!(out, "{}", render_grid(&generation.grid))?;
+    }
+
+    Ok(())
+}
+
+// PGM: floor white, empty seats grey, occupied black
+// PPM: floor light grey, empty seats green, occupied red
+fn pixel(format: ImageFormat, space: Space) -> &'static str {
+    match (format, space) {
+        (ImageFormat::Pgm, Space::Floor) => "255",
+        (ImageFormat::Pgm, Space::Empty) => "160",
+        (ImageFormat::Pgm, Space::Occupied) => "0",
+        (ImageFormat::Ppm, Space::Floor) => "224 224 224",
+        (ImageFormat::Ppm, Space::Empty) => "40 160 40",
+        (ImageFormat::Ppm, Space::Occupied) => "200 30 30",
+    }
+}
+
+pub fn render_image(grid: &[Vec<Space>], format: ImageFormat, scale: usize) -> String {
+    let columns = grid.first().map_or(0, |row| row.len());
+    image::render_grid(format, grid.len(), columns, scale, |row, column| pixel(format, grid[row][column]))
+}
+
+// writes `frame_0000.pgm`, `frame_0001.pgm`, ... into `dir`, returning the paths
+pub fn write_image_frames<I: IntoIterator<Item = Generation>>(generations: I, dir: &Path, format: ImageFormat, scale: usize) -> io::Result<Vec<PathBuf>> {
+    fs::create_dir_all(dir)?;
+
+    generations.into_iter()
+        .map(|generation| {
+            let path = dir.join(format!("frame_{:04}.{}", generation.index, format.extension()));
+            fs::write(&path, render_image(&generation.grid, format, scale))?;
+            Ok(path)
+        })
+        .collect()
 }
 
 #[aoc(day11, part1)]
 pub fn solve_part1(layout: &[Vec<Space>]) -> usize {
-    solve(layout, &count_occupied, 4)
+    solve(layout, &PART1_RULES).unwrap()
 }
 
 #[aoc(day11, part2)]
 pub fn solve_part2(layout: &[Vec<Space>]) -> usize {
-    solve(&layout, &count_occupied_sight, 5)
+    solve(layout, &PART2_RULES).unwrap()
 }
 
 #[cfg(test)]
@@ -240,8 +574,9 @@ mod tests {
              .........\n\
              #........\n\
              ...#.....");
+        let lines = SightLines::new(&layout, SightRange::Unbounded);
 
-        assert_eq!(8, count_occupied_sight(&layout, 4, 3));
+        assert_eq!(8, count_occupied_sight(&lines, &layout.concat(), 4, 3));
     }
 
     #[test]
@@ -250,9 +585,10 @@ mod tests {
             ".............\n\
              .L.L.#.#.#.#.\n\
              .............");
+        let lines = SightLines::new(&layout, SightRange::Unbounded);
 
-        assert_eq!(0, count_occupied_sight(&layout, 1, 1));
-        assert_eq!(1, count_occupied_sight(&layout, 1, 3));
+        assert_eq!(0, count_occupied_sight(&lines, &layout.concat(), 1, 1));
+        assert_eq!(1, count_occupied_sight(&lines, &layout.concat(), 1, 3));
     }
 
     #[test]
@@ -265,8 +601,9 @@ mod tests {
              ##...##\n\
              #.#.#.#\n\
              .##.##.");
+        let lines = SightLines::new(&layout, SightRange::Unbounded);
 
-        assert_eq!(0, count_occupied_sight(&layout, 3, 3));
+        assert_eq!(0, count_occupied_sight(&lines, &layout.concat(), 3, 3));
     }
 
     #[test]
@@ -285,4 +622,214 @@ mod tests {
 
         assert_eq!(26, solve_part2(&layout));
     }
-}
\ No newline at end of file
+
+    // a walk along each ray, for checking the precomputed lists
+    fn ray_walk(layout: &[Vec<Space>], i: usize, j: usize, range: SightRange) -> Vec<usize> {
+        if layout[i][j] == Space::Floor {
+            return Vec::new();
+        }
+
+        DIRECTIONS.iter()
+            .filter_map(|(di, dj)| {
+                (1..)
+                    .take_while(|k| range.reaches(*k))
+                    .map(|k| (i as i64 + di * k as i64, j as i64 + dj * k as i64))
+                    .take_while(|(ni, nj)| *ni >= 0 && *nj >= 0 && (*ni as usize) < layout.len() && (*nj as usize) < layout[0].len())
+                    .find(|(ni, nj)| layout[*ni as usize][*nj as usize] != Space::Floor)
+                    .map(|(ni, nj)| ni as usize * layout[0].len() + nj as usize)
+            })
+            .collect()
+    }
+
+    // a pseudo-random floor plan with about one seat in three
+    fn scattered_layout(rows: usize, columns: usize) -> Vec<Vec<Space>> {
+        let mut state = 0x2545_f491u64;
+        (0..rows)
+            .map(|_| (0..columns)
+                .map(|_| {
+                    state = state.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+                    if (state >> 33) % 3 == 0 { Space::Empty } else { Space::Floor }
+                })
+                .collect())
+            .collect()
+    }
+
+    #[test]
+    fn sight_lines_match_ray_walk() {
+        let layout = scattered_layout(23, 31);
+
+        for range in [SightRange::Cells(1), SightRange::Cells(3), SightRange::Unbounded].iter() {
+            let lines = SightLines::new(&layout, *range);
+            assert_eq!(lines.len(), 23 * 31);
+
+            for i in 0..layout.len() {
+                for j in 0..layout[0].len() {
+                    assert_eq!(lines.visible(lines.index(i, j)), &ray_walk(&layout, i, j, *range)[..]);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn sight_range_limits_part2() {
+        let layout = input_generator(GIVEN_INPUT_1);
+
+        // with sight limited to one cell the part 2 threshold is the only difference from part 1
+        assert_eq!(solve(&layout, &RuleSet { range: SightRange::Cells(1), tolerance: 4, ..PART2_RULES }), Some(37));
+        assert_eq!(solve(&layout, &RuleSet { range: SightRange::Cells(100), ..PART2_RULES }), Some(26));
+        assert_eq!(solve(&layout, &PART2_RULES), Some(26));
+    }
+
+    #[test]
+    fn large_layout() {
+        let layout = scattered_layout(150, 150);
+
+        let occupied = solve(&layout, &PART2_RULES).unwrap();
+        assert!(occupied > 0);
+        assert!(occupied < 150 * 150 / 3);
+    }
+
+    #[test]
+    fn simulation_history() {
+        let layout = input_generator(GIVEN_INPUT_1);
+        let history = Simulation::new(&layout, &PART1_RULES).collect::<Vec<_>>();
+
+        // the example settles after five rounds
+        assert_eq!(history.len(), 6);
+        assert_eq!(
+            history.iter().map(|g| g.occupied).collect::<Vec<_>>(),
+            vec![0, 71, 20, 51, 30, 37]);
+        assert_eq!(history[1].became_occupied, 71);
+        assert_eq!(history[2].vacated, 51);
+        assert_eq!(history[0].flipped(), 0);
+
+        assert_eq!(
+            render_grid(&history[2].grid).lines().next(),
+            Some("#.LL.L#.##"));
+    }
+
+    #[test]
+    fn text_frames() {
+        let layout = input_generator("L.\n.L");
+        let mut out = Vec::new();
+        write_text_frames(Simulation::new(&layout, &PART1_RULES), &mut out).unwrap();
+
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "generation 0: 0 occupied, 0 flipped (0 in, 0 out)\n\
+             L.\n\
+             .L\n\
+             \n\
+             generation 1: 2 occupied, 2 flipped (2 in, 0 out)\n\
+             #.\n\
+             .#\n\
+             \n");
+    }
+
+    #[test]
+    fn image_frames() {
+        let grid = input_generator("L#\n..");
+
+        assert_eq!(render_image(&grid, ImageFormat::Pgm, 1), "P2\n2 2\n255\n160 0\n255 255\n");
+        assert_eq!(
+            render_image(&grid, ImageFormat::Ppm, 2).lines().take(3).collect::<Vec<_>>(),
+            vec!["P3", "4 4", "255"]);
+
+        let dir = std::env::temp_dir().join(format!("day11_frames_{}", std::process::id()));
+        let paths = write_image_frames(
+            Simulation::new(&input_generator(GIVEN_INPUT_1), &PART2_RULES), &dir, ImageFormat::Ppm, 1).unwrap();
+
+        assert_eq!(paths.len(), 7);
+        assert_eq!(paths[0].file_name().unwrap(), "frame_0000.ppm");
+        assert!(fs::read_to_string(&paths[6]).unwrap().starts_with("P3\n10 10\n255\n"));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn hex_neighbours() {
+        let layout = input_generator("LLL\n LLL\nLLL");
+        let lines = SightLines::with_topology(&layout, Topology::Hex(HexOffset::OddRows), SightRange::Cells(1));
+
+        // an odd row leans right, an even row left
+        let mut odd = lines.visible(lines.index(1, 1)).to_vec();
+        odd.sort_unstable();
+        assert_eq!(odd, vec![1, 2, 3, 5, 7, 8]);
+
+        let mut even = lines.visible(lines.index(2, 1)).to_vec();
+        even.sort_unstable();
+        assert_eq!(even, vec![3, 4, 6, 8]);
+    }
+
+    #[test]
+    fn hex_map_parsing() {
+        let (layout, offset) = parse_hex(" L.L\nLL#\n LLL\n").unwrap();
+        assert_eq!(offset, HexOffset::EvenRows);
+        assert_eq!(layout, input_generator("L.L\nLL#\nLLL"));
+        assert_eq!(parse_hex("LLL\n LLL\nLLL"), Ok((input_generator("LLL\nLLL\nLLL"), HexOffset::OddRows)));
+
+        // an odd row leans left once the even rows are the shifted ones
+        let (layout, offset) = parse_hex(" L L L\nL L L\n L L L").unwrap();
+        let lines = SightLines::with_topology(&layout, Topology::Hex(offset), SightRange::Cells(1));
+        let mut odd = lines.visible(lines.index(1, 1)).to_vec();
+        odd.sort_unstable();
+        assert_eq!(odd, vec![0, 1, 3, 5, 6, 7]);
+
+        assert_eq!(parse_hex("LLL\nLLL"), Err("Rows 0 and 1 have the same indentation".to_string()));
+        assert_eq!(parse_hex("LLL\n LLL\n  LLL"), Err("Row=2 is indented by 2 instead of 0".to_string()));
+        assert_eq!(parse_hex("LLL\n LL"), Err("Row=1 has 2 seats instead of 3".to_string()));
+        assert_eq!(parse_hex("LxL\n LLL"), Err("Row=0 has unsupported c='x'".to_string()));
+    }
+
+    #[test]
+    fn toroidal_matches_ray_walk() {
+        for (rows, columns) in [(6, 9), (7, 5), (1, 4)].iter().cloned() {
+            let layout = scattered_layout(rows, columns);
+
+            for range in [SightRange::Cells(4), SightRange::Unbounded].iter() {
+                let lines = SightLines::with_topology(&layout, Topology::Toroidal, *range);
+
+                for i in 0..rows {
+                    for j in 0..columns {
+                        let expected = DIRECTIONS.iter()
+                            .filter(|_| layout[i][j] != Space::Floor)
+                            .filter_map(|direction| {
+                                (1..=rows * columns)
+                                    .take_while(|steps| range.reaches(*steps))
+                                    .filter_map(|steps| Topology::Toroidal.walk(rows, columns, (i, j), *direction, steps))
+                                    .take_while(|cell| *cell != (i, j))
+                                    .find(|(ni, nj)| layout[*ni][*nj] != Space::Floor)
+                                    .map(|(ni, nj)| ni * columns + nj)
+                            })
+                            .collect::<Vec<_>>();
+
+                        assert_eq!(lines.visible(lines.index(i, j)), &expected[..], "{}x{} at ({}, {})", rows, columns, i, j);
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn toroidal_wraps() {
+        let layout = input_generator("LLL\nLLL\nLLL");
+        let lines = SightLines::with_topology(&layout, Topology::Toroidal, SightRange::Unbounded);
+        assert!((0..9).all(|cell| lines.visible(cell).len() == 8));
+
+        // everyone sits, everyone leaves, forever
+        let toroidal = RuleSet { topology: Topology::Toroidal, ..PART1_RULES };
+        assert_eq!(solve(&layout, &toroidal), None);
+        assert_eq!(Simulation::new(&layout, &toroidal).count(), 3);
+        assert_eq!(solve(&layout, &PART1_RULES), Some(4));
+    }
+
+    #[test]
+    fn alternative_floor_plans() {
+        let layout = input_generator(GIVEN_INPUT_1);
+
+        assert_eq!(solve(&layout, &RuleSet { topology: Topology::Toroidal, ..PART1_RULES }), Some(30));
+        assert_eq!(solve(&layout, &RuleSet { topology: Topology::Toroidal, ..PART2_RULES }), None);
+        assert_eq!(solve(&layout, &RuleSet { topology: Topology::Hex(HexOffset::OddRows), range: SightRange::Cells(1), tolerance: 3 }), Some(34));
+        assert_eq!(solve(&layout, &RuleSet { topology: Topology::Hex(HexOffset::OddRows), range: SightRange::Unbounded, tolerance: 3 }), Some(29));
+        assert_eq!(solve(&layout, &RuleSet { topology: Topology::Hex(HexOffset::OddRows), ..PART2_RULES }), Some(39));
+    }
+}
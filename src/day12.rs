@@ -33,37 +33,48 @@ pub fn input_generator(input: &str) -> Vec<(Action, u32)> {
         .collect()
 }
 
-fn run_actions_part1(actions: &[(Action, u32)]) -> (i64, i64) {
+// ship position after every action, starting at the origin
+fn positions_part1(actions: &[(Action, u32)]) -> Vec<Point> {
     let mut y: i64 = 0;
     let mut x: i64 = 0;
     let mut d: f64 = 0.0;
 
-    for (action, value) in actions.iter().map(|(a, v)| (a, *v as i64)) {
-        match action {
-            Action::North => { y += value; },
-            Action::South => { y -= value; },
-            Action::East => { x += value; },
-            Action::West => { x -= value; },
-            Action::Forward => {
-                let d = d.to_radians();
-
-                let x_off = (value as f64 * d.cos()) as i64;
-                let y_off = (value as f64 * d.sin()) as i64;
-                x += x_off;
-                y += y_off;
-            },
-            Action::Left => { d += value as f64; },
-            Action::Right => { d -= value as f64; },
-        }
-    }
+    std::iter::once(Point::new(0, 0)).chain(
+        actions.iter()
+            .map(|(a, v)| (a, *v as i64))
+            .map(|(action, value)| {
+                match action {
+                    Action::North => { y += value; },
+                    Action::South => { y -= value; },
+                    Action::East => { x += value; },
+                    Action::West => { x -= value; },
+                    Action::Forward => {
+                        let d = d.to_radians();
+
+                        let x_off = (value as f64 * d.cos()) as i64;
+                        let y_off = (value as f64 * d.sin()) as i64;
+                        x += x_off;
+                        y += y_off;
+                    },
+                    Action::Left => { d += value as f64; },
+                    Action::Right => { d -= value as f64; },
+                }
 
+                Point::new(x, y)
+            })
+        )
+        .collect()
+}
+
+pub fn run_actions_part1(actions: &[(Action, u32)]) -> (i64, i64) {
+    let Point { x, y } = *positions_part1(actions).last().unwrap();
     (x, y)
 }
 
-#[derive(Debug, Clone, Copy, PartialEq)]
-struct Point {
-    x: i64,
-    y: i64,
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Point {
+    pub x: i64,
+    pub y: i64,
 }
 
 impl std::ops::Add<Point> for Point {
@@ -111,10 +122,14 @@ impl std::ops::Mul<i64> for Point {
 }
 
 impl Point {
-    fn new(x: i64, y: i64) -> Point {
+    pub fn new(x: i64, y: i64) -> Point {
         Point { x, y }
     }
 
+    pub fn manhattan(&self) -> u64 {
+        self.x.unsigned_abs() + self.y.unsigned_abs()
+    }
+
     fn rotate_around(&self, center: &Point, value_in_degrees: i32) -> Point {
         let s_minus_c = self - center;
 
@@ -131,7 +146,7 @@ impl Point {
     }
 }
 
-fn run_actions_part2(actions: &[(Action, u32)]) -> Vec<(Point, Point)> {
+pub fn run_actions_part2(actions: &[(Action, u32)]) -> Vec<(Point, Point)> {
     let mut ship = Point::new(0, 0);
     let mut waypoint = Point::new(10, 1);
 
@@ -169,20 +184,401 @@ fn run_actions_part2(actions: &[(Action, u32)]) -> Vec<(Point, Point)> {
         .collect::<Vec<_>>()
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Model {
+    // actions move the ship itself (part 1)
+    Direct,
+    // actions move a waypoint the ship follows (part 2)
+    Waypoint,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BoundingBox {
+    pub min: Point,
+    pub max: Point,
+}
+
+impl BoundingBox {
+    pub fn width(&self) -> u64 {
+        (self.max.x - self.min.x) as u64
+    }
+
+    pub fn height(&self) -> u64 {
+        (self.max.y - self.min.y) as u64
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Crossing {
+    // the move (index into the actions) that reaches earlier track
+    pub action: usize,
+    // the earlier move it meets
+    pub earlier: usize,
+    pub x: f64,
+    pub y: f64,
+}
+
+// a fraction `num / den` with `den > 0`, along a move
+#[derive(Debug, Clone, Copy)]
+struct Param {
+    num: i128,
+    den: i128,
+}
+
+impl Param {
+    fn new(num: i128, den: i128) -> Param {
+        if den < 0 { Param { num: -num, den: -den } } else { Param { num, den } }
+    }
+
+    fn less_than(&self, other: &Param) -> bool {
+        self.num * other.den < other.num * self.den
+    }
+
+    fn is_zero(&self) -> bool {
+        self.num == 0
+    }
+
+    fn in_unit(&self) -> bool {
+        self.num >= 0 && self.num <= self.den
+    }
+}
+
+fn cross(a: (i128, i128), b: (i128, i128)) -> i128 {
+    a.0 * b.1 - a.1 * b.0
+}
+
+// where along `p -> p + r` it first touches `q -> q + s`, as (first, last) contact
+fn contact(p: Point, r: (i128, i128), q: Point, s: (i128, i128)) -> Option<(Param, Param)> {
+    let qp = ((q.x - p.x) as i128, (q.y - p.y) as i128);
+    let rxs = cross(r, s);
+
+    if rxs != 0 {
+        let t = Param::new(cross(qp, s), rxs);
+        let u = Param::new(cross(qp, r), rxs);
+        return if t.in_unit() && u.in_unit() { Some((t, t)) } else { None };
+    }
+
+    if cross(qp, r) != 0 {
+        // parallel, never meeting
+        return None;
+    }
+
+    // collinear: project the other move onto this one
+    let rr = r.0 * r.0 + r.1 * r.1;
+    let t0 = Param::new(qp.0 * r.0 + qp.1 * r.1, rr);
+    let t1 = Param::new((qp.0 + s.0) * r.0 + (qp.1 + s.1) * r.1, rr);
+    let (lo, hi) = if t1.less_than(&t0) { (t1, t0) } else { (t0, t1) };
+
+    let zero = Param::new(0, 1);
+    let one = Param::new(1, 1);
+    if one.less_than(&lo) || hi.less_than(&zero) {
+        return None;
+    }
+
+    Some((if lo.less_than(&zero) { zero } else { lo }, if one.less_than(&hi) { one } else { hi }))
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Trajectory {
+    // the origin, then the ship after each action
+    points: Vec<Point>,
+}
+
+impl Trajectory {
+    pub fn new(actions: &[(Action, u32)], model: Model) -> Trajectory {
+        let points = match model {
+            Model::Direct => positions_part1(actions),
+            Model::Waypoint => run_actions_part2(actions).into_iter().map(|(ship, _)| ship).collect(),
+        };
+
+        Trajectory { points }
+    }
+
+    pub fn points(&self) -> &[Point] {
+        &self.points
+    }
+
+    pub fn end(&self) -> Point {
+        *self.points.last().unwrap()
+    }
+
+    pub fn bounding_box(&self) -> BoundingBox {
+        let first = self.points[0];
+        self.points.iter()
+            .fold(BoundingBox { min: first, max: first }, |b, p| BoundingBox {
+                min: Point::new(std::cmp::min(b.min.x, p.x), std::cmp::min(b.min.y, p.y)),
+                max: Point::new(std::cmp::max(b.max.x, p.x), std::cmp::max(b.max.y, p.y)),
+            })
+    }
+
+    // straight line distance sailed
+    pub fn path_length(&self) -> f64 {
+        self.points.windows(2)
+            .map(|w| {
+                let d = &w[1] - &w[0];
+                ((d.x as f64).powi(2) + (d.y as f64).powi(2)).sqrt()
+            })
+            .sum()
+    }
+
+    // the furthest point from the origin by manhattan distance, and its index in `points`
+    pub fn max_distance(&self) -> (usize, u64) {
+        self.points.iter()
+            .map(Point::manhattan)
+            .enumerate()
+            .fold((0, 0), |best, (i, d)| if d > best.1 { (i, d) } else { best })
+    }
+
+    // the first point where the ship sails back over its own track
+    pub fn first_self_crossing(&self) -> Option<Crossing> {
+        let moves = self.points.windows(2)
+            .enumerate()
+            .filter(|(_, w)| w[0] != w[1])
+            .map(|(action, w)| (action, w[0], ((w[1].x - w[0].x) as i128, (w[1].y - w[0].y) as i128)))
+            .collect::<Vec<_>>();
+
+        for (j, (action, p, r)) in moves.iter().enumerate() {
+            let mut first: Option<(Param, usize)> = None;
+
+            for (i, (earlier, q, s)) in moves[..j].iter().enumerate() {
+                let (lo, hi) = match contact(*p, *r, *q, *s) {
+                    Some(c) => c,
+                    None => continue,
+                };
+
+                // the previous move always shares our starting point
+                if i + 1 == j && lo.is_zero() && hi.is_zero() {
+                    continue;
+                }
+
+                if first.map_or(true, |(t, _)| lo.less_than(&t)) {
+                    first = Some((lo, *earlier));
+                }
+            }
+
+            if let Some((t, earlier)) = first {
+                let t = t.num as f64 / t.den as f64;
+                return Some(Crossing {
+                    action: *action,
+                    earlier,
+                    x: p.x as f64 + r.0 as f64 * t,
+                    y: p.y as f64 + r.1 as f64 * t,
+                });
+            }
+        }
+
+        None
+    }
+
+    // north is up; the start is marked green, the end red and the first crossing orange
+    pub fn to_svg(&self) -> String {
+        let bounds = self.bounding_box();
+        let size = std::cmp::max(std::cmp::max(bounds.width(), bounds.height()), 1) as f64;
+        let margin = size / 20.0;
+        let stroke = size / 400.0;
+
+        let points = self.points.iter()
+            .map(|p| format!("{},{}", p.x, -p.y))
+            .collect::<Vec<_>>()
+            .join(" ");
+
+        let circle = |x: f64, y: f64, colour: &str| {
+            format!("  <circle cx=\"{}\" cy=\"{}\" r=\"{}\" fill=\"{}\"/>\n", x, -y, stroke * 3.0, colour)
+        };
+
+        let mut out = format!(
+            "<svg xmlns=\"http://www.w3.org/2000/svg\" viewBox=\"{} {} {} {}\">\n",
+            bounds.min.x as f64 - margin, -bounds.max.y as f64 - margin,
+            bounds.width() as f64 + 2.0 * margin, bounds.height() as f64 + 2.0 * margin);
+        out += &format!(
+            "  <polyline points=\"{}\" fill=\"none\" stroke=\"navy\" stroke-width=\"{}\"/>\n", points, stroke);
+
+        let start = self.points[0];
+        out += &circle(start.x as f64, start.y as f64, "green");
+        out += &circle(self.end().x as f64, self.end().y as f64, "red");
+        if let Some(crossing) = self.first_self_crossing() {
+            out += &circle(crossing.x, crossing.y, "orange");
+        }
+
+        out += "</svg>\n";
+        out
+    }
+}
+
+// counter-clockwise quarter turns, or an error for angles the models cannot follow exactly
+fn quarter_turns(action: &Action, degrees: u32) -> Result<i64, String> {
+    if degrees % 90 != 0 {
+        return Err(format!("Rotation={:?}{} is not a multiple of 90", action, degrees));
+    }
+
+    let turns = (degrees / 90) as i64;
+    Ok(if *action == Action::Left { turns } else { -turns })
+}
+
+fn rotate_quarters(p: Point, turns: i64) -> Point {
+    match turns.rem_euclid(4) {
+        0 => p,
+        1 => Point::new(-p.y, p.x),
+        2 => Point::new(-p.x, -p.y),
+        _ => Point::new(p.y, -p.x),
+    }
+}
+
+// an action value can hold at most `u32::MAX`, so long moves are split
+fn push_move(out: &mut Vec<(Action, u32)>, action: Action, distance: u64) {
+    let mut left = distance;
+    while left > 0 {
+        let step = std::cmp::min(left, u32::MAX as u64);
+        out.push((action.clone(), step as u32));
+        left -= step;
+    }
+}
+
+fn push_translation(out: &mut Vec<(Action, u32)>, d: Point) {
+    push_move(out, if d.x < 0 { Action::West } else { Action::East }, d.x.unsigned_abs());
+    push_move(out, if d.y < 0 { Action::South } else { Action::North }, d.y.unsigned_abs());
+}
+
+fn push_rotation(out: &mut Vec<(Action, u32)>, turns: i64) {
+    match turns.rem_euclid(4) {
+        0 => {},
+        1 => out.push((Action::Left, 90)),
+        2 => out.push((Action::Right, 180)),
+        _ => out.push((Action::Right, 90)),
+    }
+}
+
+// moves commute when they act on the ship directly, so it all folds into one move each way
+// plus the net turn
+fn optimise_direct(actions: &[(Action, u32)]) -> Result<Vec<(Action, u32)>, String> {
+    let mut heading = Point::new(1, 0);
+    let mut turns = 0i64;
+    let mut d = Point::new(0, 0);
+
+    for (action, value) in actions {
+        let v = *value as i64;
+        match action {
+            Action::North => d.y += v,
+            Action::South => d.y -= v,
+            Action::East => d.x += v,
+            Action::West => d.x -= v,
+            Action::Forward => d = d + heading * v,
+            Action::Left | Action::Right => {
+                let t = quarter_turns(action, *value)?;
+                heading = rotate_quarters(heading, t);
+                turns += t;
+            },
+        }
+    }
+
+    let mut out = Vec::new();
+    push_translation(&mut out, d);
+    push_rotation(&mut out, turns);
+    Ok(out)
+}
+
+// between two `F`s the waypoint goes through `w -> rotate(w, turns) + d`, which is emitted as
+// at most one turn and two moves
+fn optimise_waypoint(actions: &[(Action, u32)]) -> Result<Vec<(Action, u32)>, String> {
+    let mut out = Vec::new();
+    let mut forward = 0u64;
+    let mut turns = 0i64;
+    let mut d = Point::new(0, 0);
+
+    for (action, value) in actions {
+        let v = *value as i64;
+        if *action == Action::Forward {
+            if v == 0 {
+                continue;
+            }
+
+            if turns.rem_euclid(4) != 0 || d != Point::new(0, 0) {
+                push_rotation(&mut out, turns);
+                push_translation(&mut out, d);
+                turns = 0;
+                d = Point::new(0, 0);
+            }
+            forward += v as u64;
+            continue;
+        }
+
+        push_move(&mut out, Action::Forward, forward);
+        forward = 0;
+
+        match action {
+            Action::North => d.y += v,
+            Action::South => d.y -= v,
+            Action::East => d.x += v,
+            Action::West => d.x -= v,
+            _ => {
+                let t = quarter_turns(action, *value)?;
+                turns += t;
+                d = rotate_quarters(d, t);
+            },
+        }
+    }
+
+    push_move(&mut out, Action::Forward, forward);
+    push_rotation(&mut out, turns);
+    push_translation(&mut out, d);
+    Ok(out)
+}
+
+// a shorter list ending in the same state (ship, and heading or waypoint) under `model`
+pub fn optimise(actions: &[(Action, u32)], model: Model) -> Result<Vec<(Action, u32)>, String> {
+    match model {
+        Model::Direct => optimise_direct(actions),
+        Model::Waypoint => optimise_waypoint(actions),
+    }
+}
+
+// degrees the ship has turned left by the end, in `0..360`
+fn final_heading(actions: &[(Action, u32)]) -> i64 {
+    actions.iter()
+        .map(|(action, value)| match action {
+            Action::Left => *value as i64,
+            Action::Right => -(*value as i64),
+            _ => 0,
+        })
+        .sum::<i64>()
+        .rem_euclid(360)
+}
+
+// runs both lists through the model and checks they end up in the same place
+pub fn verify_equivalent(original: &[(Action, u32)], optimised: &[(Action, u32)], model: Model) -> Result<(), String> {
+    let (expected, actual) = match model {
+        Model::Direct => {
+            let (heading, optimised_heading) = (final_heading(original), final_heading(optimised));
+            if heading != optimised_heading {
+                return Err(format!("Ship ends facing {} degrees instead of {}", optimised_heading, heading));
+            }
+            (run_actions_part1(original), run_actions_part1(optimised))
+        },
+        Model::Waypoint => {
+            let (ship, waypoint) = *run_actions_part2(original).last().unwrap();
+            let (optimised_ship, optimised_waypoint) = *run_actions_part2(optimised).last().unwrap();
+            if waypoint != optimised_waypoint {
+                return Err(format!("Waypoint ends at {:?} instead of {:?}", optimised_waypoint, waypoint));
+            }
+            ((ship.x, ship.y), (optimised_ship.x, optimised_ship.y))
+        },
+    };
+
+    if expected != actual {
+        return Err(format!("Ship ends at {:?} instead of {:?}", actual, expected));
+    }
+
+    Ok(())
+}
+
 #[aoc(day12, part1)]
 pub fn solve_part1(actions: &[(Action, u32)]) -> usize {
-    let (north, east) = run_actions_part1(actions);
-    north.abs() as usize + east.abs() as usize
+    Trajectory::new(actions, Model::Direct).end().manhattan() as usize
 }
 
 #[aoc(day12, part2)]
 pub fn solve_part2(actions: &[(Action, u32)]) -> usize {
-    let (Point { x: north, y: east }, _) = run_actions_part2(actions)
-        .into_iter()
-        .last()
-        .unwrap();
-
-    north.abs() as usize + east.abs() as usize
+    Trajectory::new(actions, Model::Waypoint).end().manhattan() as usize
 }
 
 #[cfg(test)]
@@ -252,7 +648,7 @@ mod tests {
 
         let (Point { x: east, y: north }, ..) = parts.iter()
             .cloned()
-            .last()
+            .next_back()
             .unwrap();
         assert_eq!(-72, north);
         assert_eq!(214, east);
@@ -289,4 +685,128 @@ mod tests {
                 (Action::Forward, 11),
             ]);
     }
-}
\ No newline at end of file
+
+    const GIVEN_INPUT: &str = "F10\nN3\nF7\nR90\nF11";
+
+    #[test]
+    fn trajectory_queries() {
+        let actions = input_generator(GIVEN_INPUT);
+
+        let direct = Trajectory::new(&actions, Model::Direct);
+        assert_eq!(direct.end(), Point::new(17, -8));
+        assert_eq!(direct.bounding_box(), BoundingBox { min: Point::new(0, -8), max: Point::new(17, 3) });
+        assert_eq!(direct.path_length(), 31.0);
+        assert_eq!(direct.max_distance(), (5, 25));
+
+        let waypoint = Trajectory::new(&actions, Model::Waypoint);
+        assert_eq!(waypoint.points().len(), 6);
+        assert_eq!(waypoint.end(), Point::new(214, -72));
+        assert_eq!(waypoint.bounding_box().height(), 110);
+        assert_eq!(waypoint.max_distance(), (5, 286));
+        assert!((waypoint.path_length() - (101f64.sqrt() * 10.0 + 116f64.sqrt() * 7.0 + 116f64.sqrt() * 11.0)).abs() < 1e-9);
+    }
+
+    #[test]
+    fn self_crossing() {
+        let actions = input_generator(GIVEN_INPUT);
+        assert_eq!(Trajectory::new(&actions, Model::Direct).first_self_crossing(), None);
+
+        // the fourth move cuts back across the first
+        let actions = input_generator("F10\nN5\nW5\nS10\nL90\nF2");
+        assert_eq!(
+            Trajectory::new(&actions, Model::Direct).first_self_crossing(),
+            Some(Crossing { action: 3, earlier: 0, x: 5.0, y: 0.0 }));
+
+        // turning straight back along the previous move
+        let actions = input_generator("N5\nS2");
+        assert_eq!(
+            Trajectory::new(&actions, Model::Direct).first_self_crossing(),
+            Some(Crossing { action: 1, earlier: 0, x: 0.0, y: 5.0 }));
+    }
+
+    #[test]
+    fn svg_export() {
+        let svg = Trajectory::new(&input_generator(GIVEN_INPUT), Model::Direct).to_svg();
+
+        assert!(svg.starts_with("<svg xmlns=\"http://www.w3.org/2000/svg\" viewBox=\"-0.85 -3.85 18.7 12.7\">\n"));
+        assert!(svg.contains("<polyline points=\"0,0 10,0 10,-3 17,-3 17,-3 17,8\""));
+        assert_eq!(svg.matches("<circle").count(), 2);
+        assert!(svg.ends_with("</svg>\n"));
+    }
+
+    // a pseudo-random action list, with only right angle turns
+    fn random_actions(seed: u64, len: usize) -> Vec<(Action, u32)> {
+        let mut state = seed;
+        (0..len)
+            .map(|_| {
+                state = state.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+                let r = state >> 33;
+                match r % 7 {
+                    0 => (Action::North, (r >> 3) as u32 % 20),
+                    1 => (Action::South, (r >> 3) as u32 % 20),
+                    2 => (Action::East, (r >> 3) as u32 % 20),
+                    3 => (Action::West, (r >> 3) as u32 % 20),
+                    4 => (Action::Forward, (r >> 3) as u32 % 20),
+                    5 => (Action::Left, 90 * ((r >> 3) as u32 % 4)),
+                    _ => (Action::Right, 90 * ((r >> 3) as u32 % 4)),
+                }
+            })
+            .collect()
+    }
+
+    #[test]
+    fn optimise_given_input() {
+        let actions = input_generator(GIVEN_INPUT);
+
+        let direct = optimise(&actions, Model::Direct).unwrap();
+        assert_eq!(direct, vec![(Action::East, 17), (Action::South, 8), (Action::Right, 90)]);
+        assert_eq!(verify_equivalent(&actions, &direct, Model::Direct), Ok(()));
+
+        let waypoint = optimise(&actions, Model::Waypoint).unwrap();
+        assert_eq!(waypoint.len(), 5);
+        assert_eq!(verify_equivalent(&actions, &waypoint, Model::Waypoint), Ok(()));
+    }
+
+    #[test]
+    fn optimise_waypoint_folds() {
+        let actions = input_generator("F2\nF3\nN1\nS1\nL90\nL180\nE4\nR270\nF0\nF1\nL360");
+
+        assert_eq!(
+            optimise(&actions, Model::Waypoint),
+            Ok(vec![(Action::Forward, 5), (Action::North, 4), (Action::Forward, 1)]));
+    }
+
+    #[test]
+    fn optimise_random_lists() {
+        for seed in 0..20 {
+            let actions = random_actions(seed, 200);
+
+            for model in [Model::Direct, Model::Waypoint].iter() {
+                let optimised = optimise(&actions, *model).unwrap();
+                assert!(optimised.len() < actions.len());
+                assert_eq!(verify_equivalent(&actions, &optimised, *model), Ok(()));
+                assert_eq!(
+                    Trajectory::new(&optimised, *model).end(),
+                    Trajectory::new(&actions, *model).end());
+            }
+        }
+    }
+
+    #[test]
+    fn optimise_rejects_odd_angles() {
+        let actions = input_generator("F10\nR45");
+        assert_eq!(optimise(&actions, Model::Direct), Err("Rotation=Right45 is not a multiple of 90".to_owned()));
+
+        let actions = input_generator("F10\nN3");
+        assert_eq!(
+            verify_equivalent(&actions, &input_generator("F10"), Model::Direct),
+            Err("Ship ends at (10, 0) instead of (10, 3)".to_owned()));
+        assert_eq!(
+            verify_equivalent(&input_generator("F10\nL90"), &input_generator("F10\nR90"), Model::Direct),
+            Err("Ship ends facing 270 degrees instead of 90".to_owned()));
+        assert_eq!(verify_equivalent(&input_generator("L270\nR45"), &input_generator("R135"), Model::Direct), Ok(()));
+        assert_eq!(
+            verify_equivalent(&actions, &input_generator("F10"), Model::Waypoint),
+            Err("Waypoint ends at Point { x: 10, y: 1 } instead of Point { x: 10, y: 4 }".to_owned()));
+    }
+}
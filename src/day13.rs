@@ -1,3 +1,8 @@
+use modular::{ gcd, solve_congruences, Congruence, CrtError };
+use std::convert::TryFrom;
+use std::error::Error;
+use std::fmt;
+
 #[derive(Debug, PartialEq)]
 pub struct BusNotes {
     first_timestamp: u32,
@@ -26,62 +31,145 @@ pub fn input_generator(input: &str) -> BusNotes {
     }
 }
 
-fn find_earliest_bus_time(bus_notes: &BusNotes) -> Option<(u32, u32)> {
-    let bus_ids = bus_notes.ids.iter()
-        .flatten()
-        .cloned()
-        .collect::<Vec<_>>();
-    let first_timestamp = bus_notes.first_timestamp;
-
-    let max_id = bus_ids.iter().max()?;
-    (first_timestamp..(first_timestamp + max_id + 1)).filter_map(|time| {
-            let id = bus_ids.iter().find(|id| time % **id == 0)?;
-            Some((*id, time))
-        })
-        .next()
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Departure {
+    pub id: u32,
+    pub time: u64,
 }
 
-#[aoc(day13, part1)]
-pub fn solve_part1(bus_notes: &BusNotes) -> u32 {
-    let (id, time) = find_earliest_bus_time(&bus_notes).unwrap();
-    let waiting_time = time - bus_notes.first_timestamp;
-    waiting_time * id
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TimetableError {
+    // a bus with id 0, at this position in the notes
+    ZeroId(usize),
+    NotInService(u32),
+    NoBuses,
+    // a time too large for the type it is reported in
+    Overflow,
+    Crt(CrtError),
+}
+
+impl fmt::Display for TimetableError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            TimetableError::ZeroId(offset) => write!(f, "Bus id=0 at offset={} never departs", offset),
+            TimetableError::NotInService(id) => write!(f, "Bus id={} is not in service", id),
+            TimetableError::NoBuses => write!(f, "No bus is in service"),
+            TimetableError::Overflow => write!(f, "Departure time does not fit"),
+            TimetableError::Crt(e) => write!(f, "{}", e),
+        }
+    }
 }
 
-fn find_first_matching_time(seed_time: u64, bus_notes: &BusNotes) -> u64 {
-    let bus_ids = &bus_notes.ids;
+impl Error for TimetableError {}
+
+// the buses in service, in the order of the notes
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Timetable {
+    // (position in the notes, id)
+    buses: Vec<(usize, u32)>,
+}
 
-    let combinations_to_check = {
-        let mut vec = bus_ids.iter()
+impl Timetable {
+    pub fn new(bus_notes: &BusNotes) -> Result<Timetable, TimetableError> {
+        let buses = bus_notes.ids.iter()
             .enumerate()
-            .flat_map(|(i, id)| id.map(|id| (i as u64, id as u64)))
+            .flat_map(|(i, id)| id.map(|id| (i, id)))
             .collect::<Vec<_>>();
 
-        vec.sort_unstable_by(|(_, a), (_, b)| b.cmp(a));
-        vec
-    };
+        if let Some((i, _)) = buses.iter().find(|(_, id)| *id == 0) {
+            return Err(TimetableError::ZeroId(*i));
+        }
 
-    let (window_offset, step) = combinations_to_check[0];
-    let combinations_to_check = &combinations_to_check[1..];
-    
-    let mut time = step * (seed_time / step + 1);
+        Ok(Timetable { buses })
+    }
 
-    loop {
-        let check_time = time - window_offset;
-        let all_good = combinations_to_check.iter()
-            .all(|(time_offset, id)| (check_time + time_offset) % id == 0);
+    pub fn ids(&self) -> Vec<u32> {
+        self.buses.iter().map(|(_, id)| *id).collect()
+    }
 
-        if all_good {
-            break check_time;
-        }
+    // first departure of bus `id` at or after `time`, None if it isn't in service or
+    // doesn't leave again before `u64::MAX`
+    pub fn next_departure(&self, id: u32, time: u64) -> Option<u64> {
+        self.buses.iter().find(|(_, bus)| *bus == id)?;
+
+        let id = id as u64;
+        time.div_ceil(id).checked_mul(id)
+    }
+
+    // the next departure of every bus that has one, soonest first
+    pub fn next_departures(&self, time: u64) -> Vec<Departure> {
+        let mut departures = self.buses.iter()
+            .flat_map(|(_, id)| self.next_departure(*id, time).map(|time| Departure { id: *id, time }))
+            .collect::<Vec<_>>();
+
+        departures.sort_by_key(|d| d.time);
+        departures
+    }
+
+    // ties go to the bus listed first
+    pub fn earliest_departure(&self, time: u64) -> Option<Departure> {
+        self.next_departures(time).into_iter().next()
+    }
+
+    // every departure in `start..end`, in time order
+    pub fn departures_between(&self, start: u64, end: u64) -> Vec<Departure> {
+        let mut departures = self.buses.iter()
+            .flat_map(|(_, id)| {
+                let first = self.next_departure(*id, start).unwrap_or(end);
+                (first..end).step_by(*id as usize).map(move |time| Departure { id: *id, time })
+            })
+            .collect::<Vec<_>>();
+
+        departures.sort_by_key(|d| d.time);
+        departures
+    }
+
+    // (id, offset) for every bus, as asked for by the contest
+    pub fn offsets(&self) -> Vec<(u32, u64)> {
+        self.buses.iter().map(|(i, id)| (*id, *i as u64)).collect()
+    }
+
+    // earliest `t` where each listed bus leaves at `t + offset`; ids may share factors
+    pub fn earliest_alignment(&self, offsets: &[(u32, u64)]) -> Result<u64, TimetableError> {
+        let congruences = offsets.iter()
+            .map(|(id, offset)| {
+                if !self.buses.iter().any(|(_, bus)| bus == id) {
+                    return Err(TimetableError::NotInService(*id));
+                }
+                Congruence::new(-(*offset as i128), *id as i128).map_err(TimetableError::Crt)
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let solution = solve_congruences(congruences).map_err(TimetableError::Crt)?;
+        u64::try_from(solution.residue).map_err(|_| TimetableError::Overflow)
+    }
 
-        time += step;
+    // every bus is back where it started after the lcm of the ids, None if that overflows
+    pub fn period(&self) -> Option<u64> {
+        self.buses.iter()
+            .try_fold(1u64, |lcm, (_, id)| (lcm / gcd(lcm as i128, *id as i128) as u64).checked_mul(*id as u64))
     }
 }
 
+fn find_earliest_bus_time(bus_notes: &BusNotes) -> Result<(u32, u32), TimetableError> {
+    let timetable = Timetable::new(bus_notes)?;
+    let departure = timetable.earliest_departure(bus_notes.first_timestamp as u64)
+        .ok_or(TimetableError::NoBuses)?;
+    let time = u32::try_from(departure.time).map_err(|_| TimetableError::Overflow)?;
+    Ok((departure.id, time))
+}
+
+#[aoc(day13, part1)]
+pub fn solve_part1(bus_notes: &BusNotes) -> Result<u32, TimetableError> {
+    let (id, time) = find_earliest_bus_time(bus_notes)?;
+    let waiting_time = time - bus_notes.first_timestamp;
+    Ok(waiting_time * id)
+}
+
 #[aoc(day13, part2)]
-pub fn solve_part2(bus_notes: &BusNotes) -> u64 {
-    find_first_matching_time(100_000_000_000_000, &bus_notes)
+pub fn solve_part2(bus_notes: &BusNotes) -> Result<u64, TimetableError> {
+    let timetable = Timetable::new(bus_notes)?;
+    timetable.earliest_alignment(&timetable.offsets())
 }
 
 #[cfg(test)]
@@ -124,7 +212,7 @@ mod tests {
     #[test]
     fn solve_part2_given1() {
         let bus_notes = input_generator(GIVEN_INPUT_1);
-        let valid_time = find_first_matching_time(0, &bus_notes);
+        let valid_time = solve_part2(&bus_notes).unwrap();
 
         assert_eq!(valid_time, 1068781);
     }
@@ -134,7 +222,7 @@ mod tests {
         let bus_notes = input_generator(
             "0\n\
              17,x,13,19");
-        let valid_time = find_first_matching_time(3400, &bus_notes);
+        let valid_time = solve_part2(&bus_notes).unwrap();
 
         assert_eq!(valid_time, 3417);
     }
@@ -144,7 +232,7 @@ mod tests {
         let bus_notes = input_generator(
             "0\n\
              67,7,59,61");
-        let valid_time = find_first_matching_time(750_000, &bus_notes);
+        let valid_time = solve_part2(&bus_notes).unwrap();
 
         assert_eq!(valid_time, 754018);
     }
@@ -154,7 +242,7 @@ mod tests {
         let bus_notes = input_generator(
             "0\n\
              67,7,x,59,61");
-        let valid_time = find_first_matching_time(0, &bus_notes);
+        let valid_time = solve_part2(&bus_notes).unwrap();
 
         assert_eq!(valid_time, 1261476);
     }
@@ -164,8 +252,81 @@ mod tests {
         let bus_notes = input_generator("\n\
             0\n\
             1789,37,47,1889");
-        let valid_time = find_first_matching_time(0, &bus_notes);
+        let valid_time = solve_part2(&bus_notes).unwrap();
 
         assert_eq!(valid_time, 1202161486);
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn solve_part2_shared_factors() {
+        // 4 and 6 share a factor: t ≡ 0 (mod 4) and t + 2 ≡ 0 (mod 6)
+        let bus_notes = input_generator("0\n4,x,6");
+        assert_eq!(solve_part2(&bus_notes), Ok(4));
+
+        // t even and t + 1 even can't both hold
+        let bus_notes = input_generator("0\n2,4");
+        assert!(matches!(solve_part2(&bus_notes), Err(TimetableError::Crt(CrtError::NoSolution(_, _)))));
+
+        let bus_notes = input_generator("0\n0,3");
+        assert_eq!(solve_part2(&bus_notes), Err(TimetableError::ZeroId(0)));
+    }
+
+    #[test]
+    fn timetable_given1() {
+        let timetable = Timetable::new(&input_generator(GIVEN_INPUT_1)).unwrap();
+
+        assert_eq!(timetable.ids(), vec![7, 13, 59, 31, 19]);
+        assert_eq!(timetable.next_departure(59, 939), Some(944));
+        assert_eq!(timetable.next_departure(7, 945), Some(945));
+        assert_eq!(timetable.next_departure(8, 939), None);
+        assert_eq!(timetable.earliest_departure(939), Some(Departure { id: 59, time: 944 }));
+        assert_eq!(timetable.next_departures(939)[1], Departure { id: 7, time: 945 });
+        assert_eq!(timetable.period(), Some(7 * 13 * 59 * 31 * 19));
+    }
+
+    #[test]
+    fn timetable_window() {
+        let timetable = Timetable::new(&input_generator(GIVEN_INPUT_1)).unwrap();
+
+        let departures = |start, end| timetable.departures_between(start, end)
+            .iter()
+            .map(|d| (d.id, d.time))
+            .collect::<Vec<_>>();
+
+        assert_eq!(departures(938, 950), vec![(7, 938), (59, 944), (7, 945), (13, 949)]);
+        assert_eq!(
+            departures(0, 15),
+            vec![(7, 0), (13, 0), (59, 0), (31, 0), (19, 0), (7, 7), (13, 13), (7, 14)]);
+        assert!(departures(10, 10).is_empty());
+
+        // no bus leaves again before u64::MAX
+        let timetable = Timetable::new(&input_generator("0\n7,13")).unwrap();
+        assert_eq!(timetable.next_departure(7, u64::MAX), None);
+        assert_eq!(timetable.next_departures(u64::MAX), vec![]);
+        assert_eq!(timetable.earliest_departure(u64::MAX - 10), Some(Departure { id: 7, time: u64::MAX - 8 }));
+        assert_eq!(timetable.departures_between(u64::MAX - 1, u64::MAX), vec![Departure { id: 7, time: u64::MAX - 1 }]);
+
+        let bus_notes = BusNotes { first_timestamp: u32::MAX - 1, ids: vec![Some(7)] };
+        assert_eq!(find_earliest_bus_time(&bus_notes), Err(TimetableError::Overflow));
+    }
+
+    #[test]
+    fn timetable_alignment() {
+        let timetable = Timetable::new(&input_generator(GIVEN_INPUT_1)).unwrap();
+
+        assert_eq!(timetable.earliest_alignment(&timetable.offsets()), Ok(1068781));
+        assert_eq!(timetable.earliest_alignment(&[(7, 0), (13, 1)]), Ok(77));
+        assert_eq!(timetable.earliest_alignment(&[]), Ok(0));
+
+        // three primes just under 2^32 line up long after u64::MAX
+        let big = Timetable::new(&input_generator("0\n4294967291,4294967279,4294967231")).unwrap();
+        assert_eq!(big.earliest_alignment(&big.offsets()), Err(TimetableError::Overflow));
+        assert_eq!(timetable.earliest_alignment(&[(8, 0)]), Err(TimetableError::NotInService(8)));
+
+        let timetable = Timetable::new(&input_generator("0\n4,6")).unwrap();
+        assert_eq!(timetable.period(), Some(12));
+        assert!(timetable.earliest_alignment(&[(4, 0), (6, 1)]).is_err());
+        assert_eq!(Timetable::new(&input_generator("0\n3,x,0")), Err(TimetableError::ZeroId(2)));
+        assert_eq!(TimetableError::ZeroId(2).to_string(), "Bus id=0 at offset=2 never departs");
+    }
+}
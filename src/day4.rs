@@ -1,6 +1,12 @@
 use std::hash::Hash;
 use std::cmp::PartialEq;
 use std::collections::HashSet;
+use std::env;
+use std::fmt;
+use std::fs;
+use std::ops::Range;
+use std::path::Path;
+use regex::Regex;
 
 #[derive(Debug, Clone, PartialEq, Hash, Eq, Copy)]
 pub enum PassportField {
@@ -15,21 +21,6 @@ pub enum PassportField {
     Unknown,
 }
 
-lazy_static! {
-    static ref VALID_COLORS: HashSet<&'static str> = {
-        let mut valid_colors = HashSet::with_capacity(7);
-        valid_colors.insert("amb");
-        valid_colors.insert("blu");
-        valid_colors.insert("brn");
-        valid_colors.insert("gry");
-        valid_colors.insert("grn");
-        valid_colors.insert("hzl");
-        valid_colors.insert("oth");
-
-        valid_colors
-    };
-}
-
 impl PassportField {
     pub fn parse(key: &str) -> Result<PassportField, String> {
         match key {
@@ -45,98 +36,879 @@ impl PassportField {
         }
     }
 
-    pub fn validate(&self, value: &str) -> Result<PassportField, String> {
+    pub fn key(&self) -> Option<&'static str> {
         match self {
-            &PassportField::BirthYear | &PassportField::IssueYear | &PassportField::ExpirationYear => {
-                let year = self.parse_u32(value)?;
-
-                let range = match self {
-                    PassportField::BirthYear => 1920..2003,
-                    PassportField::IssueYear => 2010..2021,
-                    PassportField::ExpirationYear => 2020..2031,
-                    _ => unreachable!()
-                };
+            PassportField::BirthYear => Some("byr"),
+            PassportField::IssueYear => Some("iyr"),
+            PassportField::ExpirationYear => Some("eyr"),
+            PassportField::Height => Some("hgt"),
+            PassportField::HairColor => Some("hcl"),
+            PassportField::EyeColor => Some("ecl"),
+            PassportField::PassportId => Some("pid"),
+            PassportField::CountryId => Some("cid"),
+            PassportField::Unknown => None,
+        }
+    }
+
+    // checks against the built-in schema, see `Schema::validate` for loaded ones
+    pub fn validate(&self, value: &str) -> Result<PassportField, String> {
+        let key = match self.key() {
+            Some(key) => key,
+            None => return Err(format!("{:?}: No rule for field", self)),
+        };
+
+        DEFAULT_SCHEMA.validate(key, value)
+            .map(|_| *self)
+            .map_err(|message| format!("{:?}: {}", self, message))
+    }
+}
+
+const DEFAULT_SCHEMA_SOURCE: &str = include_str!("../schema/day4.txt");
+
+const SCHEMA_ENV_VAR: &str = "DAY4_SCHEMA";
+
+lazy_static! {
+    static ref DEFAULT_SCHEMA: Schema = Schema::parse(DEFAULT_SCHEMA_SOURCE).unwrap();
+}
+
+#[derive(Debug, Clone)]
+pub enum Constraint {
+    Int(Option<Range<u32>>),
+    UnitRange(Vec<(String, Range<u32>)>),
+    Pattern(Regex),
+    OneOf(HashSet<String>),
+    Length(usize),
+}
+
+// accepts both `a..b` and `a..=b`, always stored as exclusive
+fn parse_range(range: &str) -> Result<Range<u32>, String> {
+    let (start, end, inclusive) = if let Some(idx) = range.find("..=") {
+        (&range[..idx], &range[idx + 3..], true)
+    }
+    else if let Some(idx) = range.find("..") {
+        (&range[..idx], &range[idx + 2..], false)
+    }
+    else {
+        return Err(format!("Malformed range={:?}", range));
+    };
+
+    let parse_bound = |bound: &str| bound.trim().parse::<u32>()
+        .map_err(|_| format!("Malformed range={:?}", range));
+
+    let start = parse_bound(start)?;
+    let end = parse_bound(end)?
+        .checked_add(if inclusive { 1 } else { 0 })
+        .ok_or_else(|| format!("Malformed range={:?}", range))?;
+
+    Ok(start..end)
+}
+
+fn malformed_input(value: &str) -> Result<(), String> {
+    Err(format!("Malformed input={:?}", value))
+}
+
+// ids are written back as a single batch token
+fn check_passport_id(value: &str) -> Result<(), String> {
+    if value.is_empty() || value.contains(char::is_whitespace) {
+        return malformed_input(value)
+    }
+    Ok(())
+}
 
-                if !range.contains(&year) {
-                    return self.error(format!("Invalid year range value={:?} !in {:?}", year, range))
+fn check_country_id(value: &str) -> Result<(), String> {
+    if value.contains(char::is_whitespace) {
+        return malformed_input(value)
+    }
+    Ok(())
+}
+
+fn parse_u32(value: &str) -> Result<u32, String> {
+    value.parse::<u32>()
+        .map_err(|_| format!("Failed to parse u32 value={:?}", value))
+}
+
+impl Constraint {
+    fn parse(name: &str, value: Option<&str>) -> Result<Constraint, String> {
+        match (name, value) {
+            ("int", None) => Ok(Constraint::Int(None)),
+            ("int", Some(range)) => Ok(Constraint::Int(Some(parse_range(range)?))),
+
+            ("units", Some(units)) => {
+                let units = units.split_whitespace()
+                    .map(|unit_range| {
+                        let parts = unit_range.splitn(2, ':').collect::<Vec<_>>();
+                        if parts.len() != 2 || parts[0].is_empty() {
+                            return Err(format!("Malformed unit range={:?}", unit_range));
+                        }
+
+                        Ok((parts[0].to_owned(), parse_range(parts[1])?))
+                    })
+                    .collect::<Result<Vec<_>, String>>()?;
+
+                if units.is_empty() {
+                    return Err("No units defined".to_owned());
                 }
+
+                Ok(Constraint::UnitRange(units))
             },
 
-            &PassportField::Height => {
-                let value_len = value.chars().count();
-                if value_len <= 2 {
-                    return self.malformed_input(value)
+            ("regex", Some(pattern)) => Regex::new(pattern)
+                .map(Constraint::Pattern)
+                .map_err(|e| format!("Invalid regex={:?}: {}", pattern, e)),
+
+            ("enum", Some(values)) => {
+                let values = values.split_whitespace()
+                    .map(str::to_owned)
+                    .collect::<HashSet<_>>();
+
+                if values.is_empty() {
+                    return Err("No enum values defined".to_owned());
                 }
 
-                let cm_tag = value.chars().skip(value_len - 2).collect::<String>();
-                let range = match &cm_tag[..] {
-                    "in" => 59..77,
-                    "cm" => 150..194,
-                    _ => return self.error(format!("Unknown unit value={:?}", value))
-                };
+                Ok(Constraint::OneOf(values))
+            },
 
-                let chars = value.chars().take(value_len - 2).collect::<String>();
-                let height = self.parse_u32(&chars)?;
+            ("length", Some(length)) => length.parse::<usize>()
+                .map(Constraint::Length)
+                .map_err(|_| format!("Malformed length={:?}", length)),
+
+            (name, _) => Err(format!("Unknown constraint={:?}", name)),
+        }
+    }
 
-                if !range.contains(&height) {
-                    return self.error(format!("Invalid height={:?} !in {:?}{}", value, range, cm_tag))
-                }    
+    fn check(&self, label: &str, value: &str) -> Result<(), String> {
+        match self {
+            Constraint::Int(range) => {
+                let n = parse_u32(value)?;
+
+                if let Some(range) = range {
+                    if !range.contains(&n) {
+                        return Err(format!("Invalid {} range value={:?} !in {:?}", label, n, range))
+                    }
+                }
             },
 
-            &PassportField::HairColor => {
-                if value.chars().count() != 7 {
-                    return self.malformed_input(value)
+            Constraint::UnitRange(units) => {
+                let (unit, range) = match units.iter().find(|(unit, _)| value.ends_with(&unit[..])) {
+                    Some(unit_range) => unit_range,
+                    None => return Err(format!("Unknown unit value={:?}", value)),
+                };
+
+                let number = &value[..value.len() - unit.len()];
+                if number.is_empty() {
+                    return malformed_input(value)
                 }
 
-                let first_char = value.chars().next().unwrap();
-                if first_char != '#' {
-                    return self.malformed_input(value)
+                let n = parse_u32(number)?;
+                if !range.contains(&n) {
+                    return Err(format!("Invalid {}={:?} !in {:?}{}", label, value, range, unit))
                 }
+            },
 
-                for c in value.chars().skip(1) {
-                    if !((c >= '0' && c <= '9') || (c >= 'a' && c <= 'f')) {
-                        return self.malformed_input(value)                   
-                    }
+            Constraint::Pattern(re) => {
+                if !re.is_match(value) {
+                    return malformed_input(value)
                 }
             },
 
-            &PassportField::EyeColor => {
-                if value.chars().count() != 3 {
-                    return self.malformed_input(value)
+            Constraint::OneOf(values) => {
+                if !values.contains(value) {
+                    return Err(format!("Unsupported {}={:?}", label, value))
                 }
+            },
 
-                if !VALID_COLORS.contains(value) {
-                    return self.error(format!("Unsupported eye color={:?}", value))
+            Constraint::Length(length) => {
+                if value.chars().count() != *length {
+                    return malformed_input(value)
                 }
             },
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct FieldRule {
+    key: String,
+    label: String,
+    required: bool,
+    constraints: Vec<Constraint>,
+}
+
+impl FieldRule {
+    fn new(key: &str) -> FieldRule {
+        FieldRule {
+            key: key.to_owned(),
+            label: key.to_owned(),
+            required: false,
+            constraints: Vec::new(),
+        }
+    }
+
+    pub fn key(&self) -> &str {
+        &self.key
+    }
+
+    pub fn label(&self) -> &str {
+        &self.label
+    }
+
+    pub fn is_required(&self) -> bool {
+        self.required
+    }
+
+    pub fn constraints(&self) -> &[Constraint] {
+        &self.constraints
+    }
+
+    pub fn validate(&self, value: &str) -> Result<(), String> {
+        self.constraints.iter()
+            .try_for_each(|constraint| constraint.check(&self.label, value))
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Schema {
+    rules: Vec<FieldRule>,
+}
+
+impl Schema {
+    pub fn parse(input: &str) -> Result<Schema, String> {
+        let mut rules = Vec::<FieldRule>::new();
+
+        for (line_no, line) in input.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let line_error = |message: String| format!("line {}: {}", line_no + 1, message);
+
+            if line.starts_with('[') {
+                if !line.ends_with(']') || line.len() <= 2 {
+                    return Err(line_error(format!("Malformed section={:?}", line)));
+                }
 
-            &PassportField::PassportId => {
-                if value.chars().count() != 9 {
-                    return self.malformed_input(value)
+                let key = &line[1..line.len() - 1];
+                if rules.iter().any(|r| r.key == key) {
+                    return Err(line_error(format!("Duplicate key={:?}", key)));
                 }
 
-                self.parse_u32(value)?;
+                rules.push(FieldRule::new(key));
+                continue;
+            }
+
+            let rule = match rules.last_mut() {
+                Some(rule) => rule,
+                None => return Err(line_error(format!("Entry outside of a section={:?}", line))),
+            };
+
+            let (name, value) = match line.find('=') {
+                Some(idx) => (line[..idx].trim(), Some(line[idx + 1..].trim())),
+                None => (line, None),
+            };
+
+            match (name, value) {
+                ("label", Some(label)) => rule.label = label.to_owned(),
+                ("required", None) => rule.required = true,
+                ("optional", None) => rule.required = false,
+                (name, value) => {
+                    let constraint = Constraint::parse(name, value).map_err(line_error)?;
+                    rule.constraints.push(constraint);
+                },
+            }
+        }
+
+        Ok(Schema { rules })
+    }
+
+    pub fn load(path: &Path) -> Result<Schema, String> {
+        let input = fs::read_to_string(path)
+            .map_err(|e| format!("Failed to read schema path={:?}: {}", path, e))?;
+        Schema::parse(&input)
+    }
+
+    // uses the file named by `DAY4_SCHEMA` when set, otherwise the built-in rules
+    pub fn from_env() -> Result<Schema, String> {
+        match env::var_os(SCHEMA_ENV_VAR) {
+            Some(path) => Schema::load(Path::new(&path)),
+            None => Ok(Schema::default()),
+        }
+    }
+
+    pub fn rule(&self, key: &str) -> Option<&FieldRule> {
+        self.rules.iter().find(|r| r.key == key)
+    }
+
+    pub fn rules(&self) -> &[FieldRule] {
+        &self.rules
+    }
+
+    pub fn required_keys(&self) -> impl Iterator<Item = &str> {
+        self.rules.iter()
+            .filter(|r| r.required)
+            .map(|r| r.key())
+    }
+
+    pub fn validate(&self, key: &str, value: &str) -> Result<(), String> {
+        match self.rule(key) {
+            Some(rule) => rule.validate(value),
+            None => Err(format!("Unknown key={:?}", key)),
+        }
+    }
+
+    pub fn has_required_keys(&self, fields: &[(String, String)]) -> bool {
+        self.required_keys()
+            .all(|key| fields.iter().any(|(k, _)| k == key))
+    }
+
+    pub fn check(&self, fields: &[(String, String)]) -> Vec<PassportError> {
+        let mut errors = self.required_keys()
+            .filter(|key| !fields.iter().any(|(k, _)| k == key))
+            .map(|key| PassportError::MissingField(key.to_owned()))
+            .collect::<Vec<_>>();
+
+        let mut seen = HashSet::<&str>::with_capacity(fields.len());
+        for (key, value) in fields {
+            if !seen.insert(key) {
+                errors.push(PassportError::DuplicateKey(key.clone()));
+                continue;
+            }
+
+            let rule = match self.rule(key) {
+                Some(rule) => rule,
+                None => {
+                    errors.push(PassportError::UnknownKey(key.clone()));
+                    continue;
+                },
+            };
+
+            if let Err(message) = rule.validate(value) {
+                errors.push(PassportError::InvalidValue {
+                    key: key.clone(),
+                    value: value.clone(),
+                    message,
+                });
+            }
+        }
+
+        errors
+    }
+
+    pub fn is_valid(&self, fields: &[(String, String)]) -> bool {
+        self.check(fields).iter().all(PassportError::is_warning)
+    }
+
+    pub fn report(&self, passports: &[Vec<(String, String)>]) -> ValidationReport {
+        ValidationReport {
+            passports: passports.iter()
+                .enumerate()
+                .map(|(index, fields)| PassportReport { index, errors: self.check(fields) })
+                .collect(),
+        }
+    }
+}
+
+impl Default for Schema {
+    fn default() -> Schema {
+        DEFAULT_SCHEMA.clone()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum PassportError {
+    MissingField(String),
+    UnknownKey(String),
+    DuplicateKey(String),
+    InvalidValue {
+        key: String,
+        value: String,
+        message: String,
+    },
+}
+
+impl PassportError {
+    pub fn key(&self) -> &str {
+        match self {
+            PassportError::MissingField(key)
+            | PassportError::UnknownKey(key)
+            | PassportError::DuplicateKey(key)
+            | PassportError::InvalidValue { key, .. } => key,
+        }
+    }
+
+    // reported, but a passport is still valid with them, as it was before the report existed
+    pub fn is_warning(&self) -> bool {
+        match self {
+            PassportError::UnknownKey(_) | PassportError::DuplicateKey(_) => true,
+            PassportError::MissingField(_) | PassportError::InvalidValue { .. } => false,
+        }
+    }
+
+    fn kind(&self) -> &'static str {
+        match self {
+            PassportError::MissingField(_) => "missing_field",
+            PassportError::UnknownKey(_) => "unknown_key",
+            PassportError::DuplicateKey(_) => "duplicate_key",
+            PassportError::InvalidValue { .. } => "invalid_value",
+        }
+    }
+
+    fn to_json(&self) -> String {
+        let mut json = format!("{{\"kind\":{},\"key\":{}", json_string(self.kind()), json_string(self.key()));
+        if let PassportError::InvalidValue { value, message, .. } = self {
+            json += &format!(",\"value\":{},\"message\":{}", json_string(value), json_string(message));
+        }
+        json.push('}');
+        json
+    }
+}
+
+impl fmt::Display for PassportError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            PassportError::MissingField(key) => write!(f, "missing field {}", key),
+            PassportError::UnknownKey(key) => write!(f, "unknown key {}", key),
+            PassportError::DuplicateKey(key) => write!(f, "duplicate key {}", key),
+            PassportError::InvalidValue { key, value, message } => {
+                write!(f, "invalid value {}={:?}: {}", key, value, message)
+            },
+        }
+    }
+}
+
+fn json_string(value: &str) -> String {
+    let mut result = String::with_capacity(value.len() + 2);
+    result.push('"');
+    for c in value.chars() {
+        match c {
+            '"' => result.push_str("\\\""),
+            '\\' => result.push_str("\\\\"),
+            '\n' => result.push_str("\\n"),
+            '\r' => result.push_str("\\r"),
+            '\t' => result.push_str("\\t"),
+            c if (c as u32) < 0x20 => result.push_str(&format!("\\u{:04x}", c as u32)),
+            c => result.push(c),
+        }
+    }
+    result.push('"');
+    result
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct PassportReport {
+    index: usize,
+    errors: Vec<PassportError>,
+}
+
+impl PassportReport {
+    pub fn index(&self) -> usize {
+        self.index
+    }
+
+    pub fn errors(&self) -> &[PassportError] {
+        &self.errors
+    }
+
+    pub fn is_valid(&self) -> bool {
+        self.errors.iter().all(PassportError::is_warning)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ValidationReport {
+    passports: Vec<PassportReport>,
+}
+
+impl ValidationReport {
+    pub fn passports(&self) -> &[PassportReport] {
+        &self.passports
+    }
+
+    pub fn valid_count(&self) -> usize {
+        self.passports.iter().filter(|p| p.is_valid()).count()
+    }
+
+    pub fn invalid(&self) -> impl Iterator<Item = &PassportReport> {
+        self.passports.iter().filter(|p| !p.is_valid())
+    }
+
+    pub fn render_text(&self) -> String {
+        let mut out = format!(
+            "{} passports, {} valid, {} invalid\n", 
+            self.passports.len(), 
+            self.valid_count(), 
+            self.passports.len() - self.valid_count());
+
+        for passport in self.passports.iter().filter(|p| !p.errors.is_empty()) {
+            out += &format!("passport {}:\n", passport.index);
+            for error in passport.errors.iter() {
+                out += &format!("  {}\n", error);
             }
+        }
+
+        out
+    }
+
+    pub fn render_json(&self) -> String {
+        let passports = self.passports.iter()
+            .map(|p| {
+                let errors = p.errors.iter()
+                    .map(PassportError::to_json)
+                    .collect::<Vec<_>>();
+
+                format!(
+                    "{{\"index\":{},\"valid\":{},\"errors\":[{}]}}", 
+                    p.index, 
+                    p.is_valid(), 
+                    errors.join(","))
+            })
+            .collect::<Vec<_>>();
+
+        format!(
+            "{{\"total\":{},\"valid\":{},\"passports\":[{}]}}",
+            self.passports.len(),
+            self.valid_count(),
+            passports.join(","))
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Height {
+    Centimeters(u32),
+    Inches(u32),
+}
+
+impl Height {
+    pub fn parse(value: &str) -> Result<Height, String> {
+        let (number, unit): (&str, fn(u32) -> Height) = if let Some(number) = value.strip_suffix("cm") {
+            (number, Height::Centimeters)
+        }
+        else if let Some(number) = value.strip_suffix("in") {
+            (number, Height::Inches)
+        }
+        else {
+            return Err(format!("Unknown unit value={:?}", value));
+        };
+
+        parse_u32(number).map(unit)
+    }
+}
+
+impl fmt::Display for Height {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Height::Centimeters(v) => write!(f, "{}cm", v),
+            Height::Inches(v) => write!(f, "{}in", v),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Color {
+    pub r: u8,
+    pub g: u8,
+    pub b: u8,
+}
+
+impl Color {
+    pub fn parse(value: &str) -> Result<Color, String> {
+        let hex = match value.strip_prefix('#') {
+            // from_str_radix alone would also take a sign
+            Some(hex) if hex.len() == 6 && hex.chars().all(|c| c.is_ascii_hexdigit()) => hex,
+            _ => return Err(format!("Malformed input={:?}", value)),
+        };
+
+        let channel = |i: usize| u8::from_str_radix(&hex[i..i + 2], 16)
+            .map_err(|_| format!("Malformed input={:?}", value));
+
+        Ok(Color {
+            r: channel(0)?,
+            g: channel(2)?,
+            b: channel(4)?,
+        })
+    }
+}
+
+impl fmt::Display for Color {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "#{:02x}{:02x}{:02x}", self.r, self.g, self.b)
+    }
+}
 
-            &PassportField::CountryId => (), // no validation!
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum EyeColor {
+    Amber,
+    Blue,
+    Brown,
+    Gray,
+    Green,
+    Hazel,
+    Other,
+}
 
-            &PassportField::Unknown => unreachable!(),
+impl EyeColor {
+    pub fn parse(value: &str) -> Result<EyeColor, String> {
+        match value {
+            "amb" => Ok(EyeColor::Amber),
+            "blu" => Ok(EyeColor::Blue),
+            "brn" => Ok(EyeColor::Brown),
+            "gry" => Ok(EyeColor::Gray),
+            "grn" => Ok(EyeColor::Green),
+            "hzl" => Ok(EyeColor::Hazel),
+            "oth" => Ok(EyeColor::Other),
+            _ => Err(format!("Unsupported eye color={:?}", value)),
         }
+    }
+
+    pub fn code(&self) -> &'static str {
+        match self {
+            EyeColor::Amber => "amb",
+            EyeColor::Blue => "blu",
+            EyeColor::Brown => "brn",
+            EyeColor::Gray => "gry",
+            EyeColor::Green => "grn",
+            EyeColor::Hazel => "hzl",
+            EyeColor::Other => "oth",
+        }
+    }
+}
+
+impl fmt::Display for EyeColor {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.code())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Passport {
+    pub birth_year: u32,
+    pub issue_year: u32,
+    pub expiration_year: u32,
+    pub height: Height,
+    pub hair_color: Color,
+    pub eye_color: EyeColor,
+    pub passport_id: String,
+    pub country_id: Option<String>,
+}
+
+impl Passport {
+    pub fn builder() -> PassportBuilder {
+        PassportBuilder::default()
+    }
+
+    // parses the value types only, range checks are left to a `Schema`; duplicate and unknown
+    // keys are warnings there, so they are only reported alongside a real error here
+    pub fn from_fields(fields: &[(String, String)]) -> Result<Passport, Vec<PassportError>> {
+        let mut builder = Passport::builder();
+        let mut errors = Vec::new();
+        let mut seen = HashSet::<&str>::with_capacity(fields.len());
+
+        for (key, value) in fields {
+            if !seen.insert(key) {
+                errors.push(PassportError::DuplicateKey(key.clone()));
+                continue;
+            }
+
+            let field = match PassportField::parse(key) {
+                Ok(field) => field,
+                Err(_) => {
+                    errors.push(PassportError::UnknownKey(key.clone()));
+                    continue;
+                },
+            };
+
+            if let Err(message) = builder.set(field, value) {
+                errors.push(PassportError::InvalidValue {
+                    key: key.clone(),
+                    value: value.clone(),
+                    message,
+                });
+            }
+        }
+
+        match builder.build() {
+            Ok(passport) if errors.iter().all(PassportError::is_warning) => Ok(passport),
+            Ok(_) => Err(errors),
+            Err(missing) => {
+                // a key with an invalid value is already reported
+                let missing = missing.into_iter()
+                    .filter(|m| !errors.iter().any(|e| e.key() == m.key()))
+                    .collect::<Vec<_>>();
+                errors.extend(missing);
+                Err(errors)
+            },
+        }
+    }
+
+    pub fn to_fields(&self) -> Vec<(String, String)> {
+        let mut fields = vec![
+            ("byr", self.birth_year.to_string()),
+            ("iyr", self.issue_year.to_string()),
+            ("eyr", self.expiration_year.to_string()),
+            ("hgt", self.height.to_string()),
+            ("hcl", self.hair_color.to_string()),
+            ("ecl", self.eye_color.to_string()),
+            ("pid", self.passport_id.clone()),
+        ];
+
+        if let Some(country_id) = &self.country_id {
+            fields.push(("cid", country_id.clone()));
+        }
+
+        fields.into_iter()
+            .map(|(k, v)| (k.to_owned(), v))
+            .collect()
+    }
+}
+
+// one passport per line in the batch file format
+impl fmt::Display for Passport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let pairs = self.to_fields()
+            .into_iter()
+            .map(|(k, v)| format!("{}{}{}", k, PAIR_SEPARATOR, v))
+            .collect::<Vec<_>>();
+
+        f.write_str(&pairs.join(" "))
+    }
+}
+
+pub fn write_batch(passports: &[Passport]) -> String {
+    passports.iter()
+        .map(Passport::to_string)
+        .collect::<Vec<_>>()
+        .join("\n\n")
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct PassportBuilder {
+    birth_year: Option<u32>,
+    issue_year: Option<u32>,
+    expiration_year: Option<u32>,
+    height: Option<Height>,
+    hair_color: Option<Color>,
+    eye_color: Option<EyeColor>,
+    passport_id: Option<String>,
+    country_id: Option<String>,
+}
+
+impl PassportBuilder {
+    pub fn birth_year(mut self, year: u32) -> PassportBuilder {
+        self.birth_year = Some(year);
+        self
+    }
+
+    pub fn issue_year(mut self, year: u32) -> PassportBuilder {
+        self.issue_year = Some(year);
+        self
+    }
+
+    pub fn expiration_year(mut self, year: u32) -> PassportBuilder {
+        self.expiration_year = Some(year);
+        self
+    }
+
+    pub fn height(mut self, height: Height) -> PassportBuilder {
+        self.height = Some(height);
+        self
+    }
+
+    pub fn hair_color(mut self, color: Color) -> PassportBuilder {
+        self.hair_color = Some(color);
+        self
+    }
+
+    pub fn eye_color(mut self, color: EyeColor) -> PassportBuilder {
+        self.eye_color = Some(color);
+        self
+    }
 
-        Ok(*self)
+    pub fn passport_id(mut self, id: &str) -> PassportBuilder {
+        self.passport_id = Some(id.to_owned());
+        self
     }
 
-    fn error(&self, message: String) -> Result<PassportField, String> {
-        Err(format!("{:?}: {}", self, message))
+    pub fn country_id(mut self, id: &str) -> PassportBuilder {
+        self.country_id = Some(id.to_owned());
+        self
     }
 
-    fn malformed_input(&self, value: &str) -> Result<PassportField, String> {
-        self.error(format!("Malformed input={:?}", value))
+    fn set(&mut self, field: PassportField, value: &str) -> Result<(), String> {
+        match field {
+            PassportField::BirthYear => self.birth_year = Some(parse_u32(value)?),
+            PassportField::IssueYear => self.issue_year = Some(parse_u32(value)?),
+            PassportField::ExpirationYear => self.expiration_year = Some(parse_u32(value)?),
+            PassportField::Height => self.height = Some(Height::parse(value)?),
+            PassportField::HairColor => self.hair_color = Some(Color::parse(value)?),
+            PassportField::EyeColor => self.eye_color = Some(EyeColor::parse(value)?),
+            PassportField::PassportId => {
+                check_passport_id(value)?;
+                self.passport_id = Some(value.to_owned())
+            },
+            PassportField::CountryId => {
+                check_country_id(value)?;
+                self.country_id = Some(value.to_owned())
+            },
+            PassportField::Unknown => return Err("No rule for field".to_owned()),
+        }
+
+        Ok(())
     }
 
-    fn parse_u32(&self, value: &str) -> Result<u32, String> {
-        value.parse::<u32>()
-            .map_err(|_| (format!("{:?}: Failed to parse u32 value={:?}", self, value)))
+    pub fn build(self) -> Result<Passport, Vec<PassportError>> {
+        fn missing(key: &str) -> PassportError {
+            PassportError::MissingField(key.to_owned())
+        }
+
+        fn invalid(key: &str, value: &Option<String>, check: fn(&str) -> Result<(), String>) -> Option<PassportError> {
+            let value = value.as_ref()?;
+            check(value).err().map(|message| PassportError::InvalidValue {
+                key: key.to_owned(),
+                value: value.clone(),
+                message,
+            })
+        }
+
+        // the id setters take any string, `set` has already checked parsed ones
+        let invalid = invalid("pid", &self.passport_id, check_passport_id).into_iter()
+            .chain(invalid("cid", &self.country_id, check_country_id))
+            .collect::<Vec<_>>();
+
+        match self {
+            PassportBuilder {
+                birth_year: Some(birth_year),
+                issue_year: Some(issue_year),
+                expiration_year: Some(expiration_year),
+                height: Some(height),
+                hair_color: Some(hair_color),
+                eye_color: Some(eye_color),
+                passport_id: Some(passport_id),
+                country_id,
+            } if invalid.is_empty() => Ok(Passport {
+                birth_year,
+                issue_year,
+                expiration_year,
+                height,
+                hair_color,
+                eye_color,
+                passport_id,
+                country_id,
+            }),
+
+            builder => {
+                let mut errors = invalid;
+                if builder.birth_year.is_none() { errors.push(missing("byr")); }
+                if builder.issue_year.is_none() { errors.push(missing("iyr")); }
+                if builder.expiration_year.is_none() { errors.push(missing("eyr")); }
+                if builder.height.is_none() { errors.push(missing("hgt")); }
+                if builder.hair_color.is_none() { errors.push(missing("hcl")); }
+                if builder.eye_color.is_none() { errors.push(missing("ecl")); }
+                if builder.passport_id.is_none() { errors.push(missing("pid")); }
+                Err(errors)
+            },
+        }
     }
 }
 
@@ -153,14 +925,16 @@ pub fn input_generator(input: &str) -> Vec<Vec<(String, String)>> {
             current_fields = Vec::with_capacity(8);
         }
 
+        // a pair without a separator is kept with an empty value so it can be reported
         let pairs = line.split_whitespace()
             .map(|pair| {
-                pair.split(PAIR_SEPARATOR).map(str::to_string).collect::<Vec<String>>()
+                let mut parts = pair.splitn(2, PAIR_SEPARATOR);
+                let key = parts.next().unwrap_or("").to_owned();
+                let value = parts.next().unwrap_or("").to_owned();
+                (key, value)
             });
 
-        for pair in pairs {
-            current_fields.push((pair[0].clone(), pair[1].clone()));
-        }
+        current_fields.extend(pairs);
     }
 
     result.push(current_fields);
@@ -168,55 +942,18 @@ pub fn input_generator(input: &str) -> Vec<Vec<(String, String)>> {
 }
 
 #[aoc(day4, part1)]
-pub fn solve_part1(passports: &[Vec<(String, String)>]) -> usize {
-    passports.iter()
-        .map(|fields| { 
-            fields.iter()
-                .map(|(field, _)| PassportField::parse(&field).unwrap())
-                .collect::<Vec<PassportField>>() 
-        })
-        .filter(|passport| {
-            let passport_len = passport.len();
-            if passport_len == 7 {
-                passport.iter().any(|f| f == &PassportField::CountryId)
-            }
-            else {
-                passport_len == 8
-            }
-        })
-        .count()
+pub fn solve_part1(passports: &[Vec<(String, String)>]) -> Result<usize, String> {
+    let schema = Schema::from_env()?;
+
+    Ok(passports.iter()
+        .filter(|fields| schema.has_required_keys(fields))
+        .count())
 }
 
 #[aoc(day4, part2)]
-pub fn solve_part2(passports: &[Vec<(String, String)>]) -> usize {
-    passports.iter()
-        .map(|fields| { 
-            fields.iter()
-                .map(|(key, value)| {
-                    let field = PassportField::parse(&key)?;
-                    field.validate(value)
-                })
-                .collect::<Vec<Result<PassportField, String>>>() 
-        })
-        .filter_map(|passport| {
-            let cleaned_fields = passport.into_iter()
-                .filter_map(|r| {
-                    match r {
-                        Ok(PassportField::CountryId) => None, // ignore
-
-                        Ok(f) => Some(f),
-
-                        Err(message) => { 
-                            println!("Failed to validate: {}", message); 
-                            None 
-                        }
-                    }
-                })
-                .collect::<HashSet<PassportField>>();
-
-            if cleaned_fields.len() == 7 { Some(cleaned_fields) } else { None }
-        })
-        .count()
+pub fn solve_part2(passports: &[Vec<(String, String)>]) -> Result<usize, String> {
+    let schema = Schema::from_env()?;
+    Ok(schema.report(passports).valid_count())
 }
 
 #[cfg(test)]
@@ -316,7 +1053,7 @@ mod tests {
         assert_validate_fail(PassportField::EyeColor, "hzlhzl", "Malformed input=\"hzlhzl\"");
         assert_validate_fail(PassportField::EyeColor, "aa0", "Unsupported eye color=\"aa0\"");
 
-        for color in VALID_COLORS.iter() {
+        for color in ["amb", "blu", "brn", "gry", "grn", "hzl", "oth"].iter() {
             assert_validate_success(PassportField::EyeColor, color);
         }
     }
@@ -338,4 +1075,281 @@ mod tests {
         assert_validate_success(PassportField::PassportId, "545766238");
         assert_validate_success(PassportField::PassportId, "093154719");
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn default_schema_required_keys() {
+        let schema = Schema::default();
+
+        assert_eq!(
+            schema.required_keys().collect::<Vec<_>>(),
+            vec!["byr", "iyr", "eyr", "hgt", "hcl", "ecl", "pid"]);
+        assert!(!schema.rule("cid").unwrap().is_required());
+    }
+
+    #[test]
+    fn schema_custom_rules() {
+        let schema = Schema::parse(
+            "# custom\n\
+             [byr]\n\
+             label = year\n\
+             required\n\
+             int = 1900..=1999\n\
+             \n\
+             [hgt]\n\
+             optional\n\
+             units = m:1..3 ft:3..8\n\
+             \n\
+             [ecl]\n\
+             label = eye color\n\
+             enum = red green").unwrap();
+
+        assert_eq!(schema.validate("byr", "1999"), Ok(()));
+        assert_eq!(schema.validate("byr", "2000"), Err("Invalid year range value=2000 !in 1900..2000".to_owned()));
+        assert_eq!(schema.validate("hgt", "2m"), Ok(()));
+        assert_eq!(schema.validate("hgt", "9ft"), Err("Invalid hgt=\"9ft\" !in 3..8ft".to_owned()));
+        assert_eq!(schema.validate("hgt", "ft"), Err("Malformed input=\"ft\"".to_owned()));
+        assert_eq!(schema.validate("ecl", "blu"), Err("Unsupported eye color=\"blu\"".to_owned()));
+        assert_eq!(schema.validate("pid", "1"), Err("Unknown key=\"pid\"".to_owned()));
+
+        assert_eq!(schema.required_keys().collect::<Vec<_>>(), vec!["byr"]);
+    }
+
+    #[test]
+    fn schema_parse_errors() {
+        fn parse_err(input: &str) -> String {
+            Schema::parse(input).unwrap_err()
+        }
+
+        assert_eq!(parse_err("required"), "line 1: Entry outside of a section=\"required\"");
+        assert_eq!(parse_err("[byr]\nint = 10"), "line 2: Malformed range=\"10\"");
+        assert_eq!(parse_err("[byr]\nint = 0..=4294967295"), "line 2: Malformed range=\"0..=4294967295\"");
+        assert_eq!(parse_err("[byr]\nfoo = 10"), "line 2: Unknown constraint=\"foo\"");
+        assert_eq!(parse_err("[byr]\n[byr]"), "line 2: Duplicate key=\"byr\"");
+        assert_eq!(parse_err("[hgt]\nunits = cm"), "line 2: Malformed unit range=\"cm\"");
+    }
+
+    const GIVEN_INPUT_PART2: &str = "eyr:1972 cid:100\n\
+        hcl:#18171d ecl:amb hgt:170 pid:186cm iyr:2018 byr:1926\n\
+        \n\
+        iyr:2019\n\
+        hcl:#602927 eyr:1967 hgt:170cm\n\
+        ecl:grn pid:012533040 byr:1946\n\
+        \n\
+        pid:087499704 hgt:74in ecl:grn iyr:2012 eyr:2030 byr:1980\n\
+        hcl:#623a2f\n\
+        \n\
+        eyr:2029 ecl:blu cid:129 byr:1989\n\
+        iyr:2014 pid:896056539 hcl:#a97842 hgt:165cm\n\
+        \n\
+        hcl:#888785\n\
+        hgt:164cm byr:2001 iyr:2015 cid:88\n\
+        pid:545766238 ecl:hzl\n\
+        eyr:2022";
+
+    #[test]
+    fn solve_given_input() {
+        let passports = input_generator(GIVEN_INPUT_PART2);
+
+        assert_eq!(solve_part1(&passports), Ok(5));
+        assert_eq!(solve_part2(&passports), Ok(3));
+    }
+
+    #[test]
+    fn report_lists_typed_errors() {
+        let passports = input_generator(
+            "byr:1920 iyr:2010 eyr:2020 hgt:150cm hcl:#123abc ecl:amb pid:000000001\n\
+             \n\
+             byr:1920 iyr:2010 eyr:2020 hgt:200 hcl:#123abc ecl:amb\n\
+             \n\
+             byr:1920 byr:1921 iyr:2010 eyr:2020 hgt:150cm hcl:#123abc ecl:amb pid:000000001 foo:bar");
+
+        let report = Schema::default().report(&passports);
+
+        // unknown and duplicate keys are reported without failing the passport
+        assert_eq!(report.valid_count(), 2);
+        assert!(report.passports()[0].is_valid());
+        assert!(report.passports()[2].is_valid());
+        assert_eq!(
+            report.passports()[1].errors(),
+            &[
+                PassportError::MissingField("pid".to_owned()),
+                PassportError::InvalidValue {
+                    key: "hgt".to_owned(),
+                    value: "200".to_owned(),
+                    message: "Unknown unit value=\"200\"".to_owned(),
+                },
+            ]);
+        assert_eq!(
+            report.passports()[2].errors(),
+            &[
+                PassportError::DuplicateKey("byr".to_owned()),
+                PassportError::UnknownKey("foo".to_owned()),
+            ]);
+        assert!(report.render_text().ends_with("passport 2:\n  duplicate key byr\n  unknown key foo\n"));
+    }
+
+    #[test]
+    fn unknown_key_does_not_panic() {
+        let passports = input_generator("byr:1920 iyr:2010 eyr:2020 hgt:150cm hcl:#123abc ecl:amb pid:000000001 zzz");
+
+        assert_eq!(passports[0].last(), Some(&("zzz".to_owned(), "".to_owned())));
+        assert_eq!(solve_part1(&passports), Ok(1));
+        assert_eq!(solve_part2(&passports), Ok(1));
+    }
+
+    #[test]
+    fn report_renderers() {
+        let passports = input_generator(
+            "byr:1920 iyr:2010 eyr:2020 hgt:150cm hcl:#123abc ecl:amb pid:000000001\n\
+             \n\
+             byr:1920 iyr:2010 eyr:2020 hgt:150cm hcl:#123abc ecl:amb pid:1");
+
+        let report = Schema::default().report(&passports);
+
+        assert_eq!(
+            report.render_text(),
+            "2 passports, 1 valid, 1 invalid\n\
+             passport 1:\n  \
+               invalid value pid=\"1\": Malformed input=\"1\"\n");
+
+        assert_eq!(
+            report.render_json(),
+            concat!(
+                r#"{"total":2,"valid":1,"passports":["#,
+                r#"{"index":0,"valid":true,"errors":[]},"#,
+                r#"{"index":1,"valid":false,"errors":["#,
+                r#"{"kind":"invalid_value","key":"pid","value":"1","message":"Malformed input=\"1\""}"#,
+                r#"]}]}"#));
+    }
+
+    #[test]
+    fn passport_builder() {
+        let passport = Passport::builder()
+            .birth_year(1980)
+            .issue_year(2012)
+            .expiration_year(2030)
+            .height(Height::Inches(74))
+            .hair_color(Color { r: 0x62, g: 0x3a, b: 0x2f })
+            .eye_color(EyeColor::Green)
+            .passport_id("087499704")
+            .build()
+            .unwrap();
+
+        assert_eq!(
+            passport.to_string(),
+            "byr:1980 iyr:2012 eyr:2030 hgt:74in hcl:#623a2f ecl:grn pid:087499704");
+
+        assert_eq!(
+            Passport::builder().birth_year(1980).eye_color(EyeColor::Blue).build(),
+            Err(["iyr", "eyr", "hgt", "hcl", "pid"].iter()
+                .map(|k| PassportError::MissingField(k.to_string()))
+                .collect()));
+
+        // `write_batch` would split these into two pairs
+        let spaced = |pid: &str, cid: &str| Passport::builder()
+            .birth_year(1980)
+            .issue_year(2012)
+            .expiration_year(2030)
+            .height(Height::Inches(74))
+            .hair_color(Color { r: 0x62, g: 0x3a, b: 0x2f })
+            .eye_color(EyeColor::Green)
+            .passport_id(pid)
+            .country_id(cid)
+            .build();
+
+        assert!(spaced("087499704", "").is_ok());
+        assert_eq!(
+            spaced("0874 99704", "a\tb"),
+            Err(vec![
+                PassportError::InvalidValue {
+                    key: "pid".to_owned(),
+                    value: "0874 99704".to_owned(),
+                    message: "Malformed input=\"0874 99704\"".to_owned(),
+                },
+                PassportError::InvalidValue {
+                    key: "cid".to_owned(),
+                    value: "a\tb".to_owned(),
+                    message: "Malformed input=\"a\\tb\"".to_owned(),
+                },
+            ]));
+        assert_eq!(
+            spaced("", "7"),
+            Err(vec![
+                PassportError::InvalidValue {
+                    key: "pid".to_owned(),
+                    value: "".to_owned(),
+                    message: "Malformed input=\"\"".to_owned(),
+                },
+            ]));
+    }
+
+    #[test]
+    fn passport_typed_values() {
+        assert_eq!(Height::parse("150cm"), Ok(Height::Centimeters(150)));
+        assert_eq!(Height::parse("59in"), Ok(Height::Inches(59)));
+        assert_eq!(Height::parse("59"), Err("Unknown unit value=\"59\"".to_owned()));
+        assert_eq!(Color::parse("#A0B1c2"), Ok(Color { r: 0xa0, g: 0xb1, b: 0xc2 }));
+        assert_eq!(Color::parse("#a0b1c"), Err("Malformed input=\"#a0b1c\"".to_owned()));
+        assert_eq!(Color::parse("#+f+f+f"), Err("Malformed input=\"#+f+f+f\"".to_owned()));
+        assert_eq!(EyeColor::parse("hzl"), Ok(EyeColor::Hazel));
+        assert_eq!(EyeColor::parse("xyz"), Err("Unsupported eye color=\"xyz\"".to_owned()));
+    }
+
+    #[test]
+    fn passport_round_trip() {
+        let passports = input_generator(GIVEN_INPUT_PART2)
+            .iter()
+            .filter_map(|fields| Passport::from_fields(fields).ok())
+            .collect::<Vec<_>>();
+        assert_eq!(passports.len(), 4); // only `hgt:170` fails to parse, ranges are not checked
+
+        let reparsed = input_generator(&write_batch(&passports))
+            .iter()
+            .map(|fields| Passport::from_fields(fields).unwrap())
+            .collect::<Vec<_>>();
+
+        assert_eq!(passports, reparsed);
+    }
+
+    #[test]
+    fn passport_normalises_values() {
+        let fields = input_generator("hcl:#ABCDEF ecl:amb\nbyr:1920 iyr:2010\neyr:2020 hgt:0150cm pid:000000001 cid:7")
+            .remove(0);
+        let passport = Passport::from_fields(&fields).unwrap();
+
+        assert_eq!(
+            passport.to_string(),
+            "byr:1920 iyr:2010 eyr:2020 hgt:150cm hcl:#abcdef ecl:amb pid:000000001 cid:7");
+        assert!(Schema::default().is_valid(&passport.to_fields()));
+    }
+
+    #[test]
+    fn passport_from_fields_errors() {
+        let fields = input_generator("byr:abc hgt:150 ecl:amb ecl:blu").remove(0);
+
+        assert_eq!(
+            Passport::from_fields(&fields),
+            Err(vec![
+                PassportError::InvalidValue {
+                    key: "byr".to_owned(),
+                    value: "abc".to_owned(),
+                    message: "Failed to parse u32 value=\"abc\"".to_owned(),
+                },
+                PassportError::InvalidValue {
+                    key: "hgt".to_owned(),
+                    value: "150".to_owned(),
+                    message: "Unknown unit value=\"150\"".to_owned(),
+                },
+                PassportError::DuplicateKey("ecl".to_owned()),
+                PassportError::MissingField("iyr".to_owned()),
+                PassportError::MissingField("eyr".to_owned()),
+                PassportError::MissingField("hcl".to_owned()),
+                PassportError::MissingField("pid".to_owned()),
+            ]));
+
+        // warnings alone don't fail it, as in a schema check, and the first value wins
+        let fields = input_generator("byr:1920 iyr:2010 eyr:2020 hgt:150cm hcl:#123abc ecl:amb ecl:blu pid:000000001 foo:bar")
+            .remove(0);
+        assert_eq!(Passport::from_fields(&fields).map(|p| p.eye_color), Ok(EyeColor::Amber));
+    }
+}
@@ -1,6 +1,9 @@
 use std::hash::Hash;
 use std::cmp::PartialEq;
+use std::error::Error;
+use std::fmt;
 use bit_vec::BitVec;
+use image::{ self, ImageFormat };
 use std::ops::Range;
 
 #[derive(Debug, Clone, PartialEq, Hash, Eq, Copy)]
@@ -12,142 +15,399 @@ pub enum Direction {
 }
 
 impl Direction {
-    pub fn parse(d: char) -> Direction {
+    pub fn parse(d: char) -> Option<Direction> {
         match d {
-            'F' => Direction::Front,
-            'B' => Direction::Back,
-            'L' => Direction::Left,
-            'R' => Direction::Right,
-            _ => unreachable!(format!("Undefined input={:?}", d))
+            'F' => Some(Direction::Front),
+            'B' => Some(Direction::Back),
+            'L' => Some(Direction::Left),
+            'R' => Some(Direction::Right),
+            _ => None,
+        }
+    }
+
+    pub fn letter(&self) -> char {
+        match self {
+            Direction::Front => 'F',
+            Direction::Back => 'B',
+            Direction::Left => 'L',
+            Direction::Right => 'R',
         }
     }
 }
 
 #[aoc_generator(day5)]
-pub fn input_generator(input: &str) -> Vec<Vec<Direction>> {
+pub fn input_generator(input: &str) -> Result<Vec<Vec<Direction>>, SeatError> {
     input.lines()
-        .map(|line| line.chars().map(Direction::parse).collect::<Vec<Direction>>())
-        .collect::<Vec<Vec<Direction>>>()
+        .map(|line| {
+            line.chars()
+                .enumerate()
+                .map(|(position, letter)| Direction::parse(letter).ok_or(SeatError::InvalidLetter { position, letter }))
+                .collect::<Result<Vec<Direction>, _>>()
+        })
+        .collect()
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SeatError {
+    EmptyLayout,
+    WrongLength { expected: usize, actual: usize },
+    InvalidLetter { position: usize, letter: char },
+    OutOfRange { row: usize, column: usize },
+    NoPasses,
+    // part 2 needs exactly one free seat with both neighbours taken
+    AmbiguousSeat(Vec<usize>),
+}
+
+impl fmt::Display for SeatError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SeatError::EmptyLayout => write!(f, "Layout must have at least one row and column"),
+            SeatError::WrongLength { expected, actual } => {
+                write!(f, "Expected {} partitions, found {}", expected, actual)
+            },
+            SeatError::InvalidLetter { position, letter } => {
+                write!(f, "Invalid partition={:?} at position={}", letter, position)
+            },
+            SeatError::OutOfRange { row, column } => {
+                write!(f, "Seat row={}, column={} is outside of the layout", row, column)
+            },
+            SeatError::NoPasses => write!(f, "No boarding passes to check"),
+            SeatError::AmbiguousSeat(ids) => write!(f, "Expected a single isolated free seat, found {:?}", ids),
+        }
+    }
+}
+
+impl Error for SeatError {}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Seat {
+    pub row: usize,
+    pub column: usize,
+}
+
+// number of partitions needed to address `count` values
+fn partition_bits(count: usize) -> usize {
+    (std::mem::size_of::<usize>() * 8) - (count - 1).leading_zeros() as usize
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SeatLayout {
+    rows: usize,
+    columns: usize,
+    row_bits: usize,
+    column_bits: usize,
 }
 
-const ROW_PARTITION_COUNT: usize = 7;
 const ROW_COUNT: usize = 128;
 const COLUMN_COUNT: usize = 8;
-const TOTAL_VALUES: usize = ROW_COUNT * COLUMN_COUNT;
-
-pub fn calculate_seat_id(description: &[Direction]) -> usize {
-    let row = description.iter()
-        .take(ROW_PARTITION_COUNT)
-        .fold(0..ROW_COUNT, |current_row_range, partition| {
-            let partition_index = current_row_range.start + (current_row_range.end - current_row_range.start) / 2;
-            match partition {
-                Direction::Front => current_row_range.start..partition_index,
-                Direction::Back => partition_index..current_row_range.end,
-                _ => unreachable!(format!("Undefined partition={:?}", partition)),
-            }
-        }).start;
-    let column = description.iter()
-        .skip(ROW_PARTITION_COUNT)
-        .fold(0..COLUMN_COUNT, |current_column_range, partition| {
-            let partition_index = current_column_range.start + (current_column_range.end - current_column_range.start) / 2;
-            match partition {
-                Direction::Left => current_column_range.start..partition_index,
-                Direction::Right => partition_index..current_column_range.end,
-                _ => unreachable!(format!("Undefined partition={:?}", partition)),
-            }
-        }).start;
-    
-    // seat_id
-    row * COLUMN_COUNT + column
+
+impl Default for SeatLayout {
+    fn default() -> SeatLayout {
+        SeatLayout::new(ROW_COUNT, COLUMN_COUNT).unwrap()
+    }
 }
 
-fn id_at(row: usize, column: usize) -> usize {
-    row * COLUMN_COUNT + column
+impl SeatLayout {
+    pub fn new(rows: usize, columns: usize) -> Result<SeatLayout, SeatError> {
+        if rows == 0 || columns == 0 {
+            return Err(SeatError::EmptyLayout);
+        }
+
+        Ok(SeatLayout {
+            rows,
+            columns,
+            row_bits: partition_bits(rows),
+            column_bits: partition_bits(columns),
+        })
+    }
+
+    pub fn rows(&self) -> usize {
+        self.rows
+    }
+
+    pub fn columns(&self) -> usize {
+        self.columns
+    }
+
+    pub fn pass_len(&self) -> usize {
+        self.row_bits + self.column_bits
+    }
+
+    pub fn seat_count(&self) -> usize {
+        self.rows * self.columns
+    }
+
+    pub fn seat_id(&self, seat: &Seat) -> usize {
+        seat.row * self.columns + seat.column
+    }
+
+    pub fn seat_at(&self, id: usize) -> Seat {
+        Seat { row: id / self.columns, column: id % self.columns }
+    }
+
+    pub fn decode(&self, pass: &str) -> Result<Seat, SeatError> {
+        let actual = pass.chars().count();
+        if actual != self.pass_len() {
+            return Err(SeatError::WrongLength { expected: self.pass_len(), actual });
+        }
+
+        let description = pass.chars()
+            .enumerate()
+            .map(|(position, letter)| Direction::parse(letter).ok_or(SeatError::InvalidLetter { position, letter }))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        self.decode_directions(&description)
+    }
+
+    pub fn decode_directions(&self, description: &[Direction]) -> Result<Seat, SeatError> {
+        if description.len() != self.pass_len() {
+            return Err(SeatError::WrongLength { expected: self.pass_len(), actual: description.len() });
+        }
+
+        let bits = description.iter()
+            .enumerate()
+            .try_fold(0usize, |bits, (position, d)| {
+                let bit = match (position < self.row_bits, d) {
+                    (true, Direction::Front) | (false, Direction::Left) => 0,
+                    (true, Direction::Back) | (false, Direction::Right) => 1,
+                    _ => return Err(SeatError::InvalidLetter { position, letter: d.letter() }),
+                };
+
+                Ok((bits << 1) | bit)
+            })?;
+
+        let seat = Seat {
+            row: bits >> self.column_bits,
+            column: bits & ((1 << self.column_bits) - 1),
+        };
+
+        if seat.row >= self.rows || seat.column >= self.columns {
+            return Err(SeatError::OutOfRange { row: seat.row, column: seat.column });
+        }
+
+        Ok(seat)
+    }
+
+    pub fn encode(&self, seat: &Seat) -> Result<String, SeatError> {
+        if seat.row >= self.rows || seat.column >= self.columns {
+            return Err(SeatError::OutOfRange { row: seat.row, column: seat.column });
+        }
+
+        let row = (0..self.row_bits).rev()
+            .map(|bit| if seat.row >> bit & 1 == 1 { Direction::Back } else { Direction::Front });
+        let column = (0..self.column_bits).rev()
+            .map(|bit| if seat.column >> bit & 1 == 1 { Direction::Right } else { Direction::Left });
+
+        Ok(row.chain(column).map(|d| d.letter()).collect())
+    }
 }
 
-fn id_to(id: &usize) -> (usize, usize) {
-    (*id / COLUMN_COUNT, *id % COLUMN_COUNT)
+pub fn calculate_seat_id(description: &[Direction]) -> Result<usize, SeatError> {
+    let layout = SeatLayout::default();
+    let seat = layout.decode_directions(description)?;
+
+    Ok(layout.seat_id(&seat))
 }
 
 #[aoc(day5, part1)]
-pub fn solve_part1(seat_descriptions: &[Vec<Direction>]) -> usize {
-    seat_descriptions.iter()
-        .map(|desc| calculate_seat_id(&desc))
-        .max()
-        .unwrap()
+pub fn solve_part1(seat_descriptions: &[Vec<Direction>]) -> Result<usize, SeatError> {
+    let ids = seat_descriptions.iter()
+        .map(|desc| calculate_seat_id(desc))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    ids.into_iter().max().ok_or(SeatError::NoPasses)
 }
 
-fn load_id_map_with_only_valid_values(marked_ranges: &[Range<usize>]) -> BitVec {
-    let mut invalid_markers = BitVec::with_capacity(TOTAL_VALUES);
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VacantRun {
+    pub ids: Range<usize>,
+    // occupied seats on both sides of the run
+    pub bounded: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Vacancies {
+    pub runs: Vec<VacantRun>,
+    pub missing_front_rows: Range<usize>,
+    pub missing_back_rows: Range<usize>,
+    pub duplicate_ids: Vec<usize>,
+}
 
-    for i in 0..id_at(ROW_COUNT - 1, COLUMN_COUNT) {
-        invalid_markers.push(marked_ranges.iter().any(|r| r.contains(&i)))
+impl Vacancies {
+    pub fn vacant_ids(&self) -> impl Iterator<Item = usize> + '_ {
+        self.runs.iter().flat_map(|run| run.ids.clone())
     }
 
-    invalid_markers
+    // single free seats with both neighbours taken
+    pub fn isolated_ids(&self) -> impl Iterator<Item = usize> + '_ {
+        self.runs.iter()
+            .filter(|run| run.bounded && run.ids.len() == 1)
+            .map(|run| run.ids.start)
+    }
 }
 
-fn print_table(markers: &BitVec) {
-    if !cfg!(debug_assertions) {
-        return;
-    }
+#[derive(Debug, Clone)]
+pub struct SeatMap {
+    layout: SeatLayout,
+    occupied: BitVec,
+    duplicate_ids: Vec<usize>,
+}
+
+const OCCUPIED_MARKER: char = '#';
+const VACANT_MARKER: char = '.';
+const DUPLICATE_MARKER: char = '!';
+const AISLE_MARKER: char = ' ';
 
-    for (id, v) in markers.iter().enumerate() {
-        let (row, column) = id_to(&id);
-        if column == 0 {
-            if id > 0 {
-                println!("|");
+impl SeatMap {
+    pub fn new(layout: SeatLayout, seats: &[Seat]) -> Result<SeatMap, SeatError> {
+        let mut occupied = BitVec::from_elem(layout.seat_count(), false);
+        let mut duplicate_ids = Vec::new();
+
+        for seat in seats {
+            // a column past the end would still give an id inside the cabin, just the wrong one
+            if seat.row >= layout.rows() || seat.column >= layout.columns() {
+                return Err(SeatError::OutOfRange { row: seat.row, column: seat.column });
+            }
+
+            let id = layout.seat_id(seat);
+            if occupied[id] {
+                duplicate_ids.push(id);
             }
 
-            print!("R{:>03} |", row);
+            occupied.set(id, true);
         }
 
-        print!("{}", v as i32);
+        duplicate_ids.sort_unstable();
+        duplicate_ids.dedup();
+
+        Ok(SeatMap {
+            layout,
+            occupied,
+            duplicate_ids,
+        })
     }
 
-    println!("!");
-}
+    pub fn from_descriptions(layout: SeatLayout, descriptions: &[Vec<Direction>]) -> Result<SeatMap, SeatError> {
+        let seats = descriptions.iter()
+            .map(|desc| layout.decode_directions(desc))
+            .collect::<Result<Vec<_>, _>>()?;
 
-#[aoc(day5, part2)]
-pub fn solve_part2(seat_descriptions: &[Vec<Direction>]) -> usize {
-    let valid_values = id_at(1, 0)..id_at(ROW_COUNT - 1, COLUMN_COUNT);
+        SeatMap::new(layout, &seats)
+    }
+
+    pub fn layout(&self) -> &SeatLayout {
+        &self.layout
+    }
 
-    let given_ids = seat_descriptions.iter()
-        .filter_map(|desc| {
-            let id = calculate_seat_id(&desc);
-            if valid_values.contains(&id) { Some(id) } else { None }
-        });
+    pub fn is_occupied(&self, id: usize) -> bool {
+        self.occupied.get(id).unwrap_or(false)
+    }
 
+    fn is_row_empty(&self, row: usize) -> bool {
+        let columns = self.layout.columns();
+        (row * columns..(row + 1) * columns).all(|id| !self.occupied[id])
+    }
 
-    let invalid_ranges = vec![
-        0..COLUMN_COUNT,
-        id_at(ROW_COUNT - 1, 0)..id_at(ROW_COUNT - 1, COLUMN_COUNT)
-    ];
+    pub fn vacancies(&self) -> Vacancies {
+        let mut runs = Vec::new();
 
-    let mut invalid_markers = load_id_map_with_only_valid_values(&invalid_ranges);
+        let mut run_start = None;
+        for id in 0..=self.layout.seat_count() {
+            let occupied = id == self.layout.seat_count() || self.occupied[id];
+
+            match (run_start, occupied) {
+                (None, false) => run_start = Some(id),
+                (Some(start), true) => {
+                    runs.push(VacantRun {
+                        ids: start..id,
+                        bounded: start > 0 && id < self.layout.seat_count(),
+                    });
+                    run_start = None;
+                },
+                _ => {},
+            }
+        }
+
+        let rows = self.layout.rows();
+        let front = (0..rows).take_while(|row| self.is_row_empty(*row)).count();
+        let back = (front..rows).rev().take_while(|row| self.is_row_empty(*row)).count();
+
+        Vacancies {
+            runs,
+            missing_front_rows: 0..front,
+            missing_back_rows: (rows - back)..rows,
+            duplicate_ids: self.duplicate_ids.clone(),
+        }
+    }
 
-    for given_id in given_ids {
-        invalid_markers.set(given_id, true);
+    fn marker(&self, id: usize) -> char {
+        if self.duplicate_ids.binary_search(&id).is_ok() {
+            DUPLICATE_MARKER
+        }
+        else if self.occupied[id] {
+            OCCUPIED_MARKER
+        }
+        else {
+            VACANT_MARKER
+        }
     }
 
-    print_table(&invalid_markers);
-    
-    let result = invalid_markers.iter()
-        .enumerate()
-        .filter_map(|(i, v)| if !v { Some(i) } else { None })
-        .find(|check_idx| {
-            invalid_markers[check_idx - 1] && invalid_markers[check_idx + 1]
-        }) // -1 / +1 are set
-        .unwrap();
+    // `aisles` are the column indices an aisle is drawn before
+    pub fn render_text(&self, aisles: &[usize]) -> String {
+        let label_width = (self.layout.rows() - 1).to_string().len();
+
+        let with_aisles = |column: usize, c: char| {
+            if aisles.contains(&column) { format!("{}{}", AISLE_MARKER, c) } else { c.to_string() }
+        };
+
+        let header = (0..self.layout.columns())
+            .map(|column| {
+                let label = if self.layout.columns() <= 26 {
+                    (b'A' + column as u8) as char
+                } else {
+                    std::char::from_digit((column % 10) as u32, 10).unwrap()
+                };
+                with_aisles(column, label)
+            })
+            .collect::<String>();
+
+        let mut out = format!("{:width$} {}\n", "", header, width = label_width);
+        for row in 0..self.layout.rows() {
+            let seats = (0..self.layout.columns())
+                .map(|column| with_aisles(column, self.marker(self.layout.seat_id(&Seat { row, column }))))
+                .collect::<String>();
+
+            out += &format!("{:>width$} {}\n", row, seats, width = label_width);
+        }
+
+        out
+    }
 
-    print_table(&invalid_markers);
+    pub fn default_aisles(&self) -> Vec<usize> {
+        if self.layout.columns() > 1 { vec![self.layout.columns() / 2] } else { Vec::new() }
+    }
 
-    if cfg!(debug_assertions) {
-        let (row, column) = id_to(&result);
-        println!("row={}, column={}, id={}", row, column, result);
+    // greyscale, one `scale`x`scale` block per seat
+    pub fn render_pgm(&self, scale: usize) -> String {
+        image::render_grid(ImageFormat::Pgm, self.layout.rows(), self.layout.columns(), scale, |row, column| {
+            match self.marker(self.layout.seat_id(&Seat { row, column })) {
+                OCCUPIED_MARKER => "0",
+                DUPLICATE_MARKER => "128",
+                _ => "255",
+            }
+        })
     }
+}
 
-    result
+#[aoc(day5, part2)]
+pub fn solve_part2(seat_descriptions: &[Vec<Direction>]) -> Result<usize, SeatError> {
+    let seat_map = SeatMap::from_descriptions(SeatLayout::default(), seat_descriptions)?;
+
+    let isolated = seat_map.vacancies()
+        .isolated_ids()
+        .collect::<Vec<_>>();
+
+    if isolated.len() == 1 { Ok(isolated[0]) } else { Err(SeatError::AmbiguousSeat(isolated)) }
 }
 
 #[cfg(test)]
@@ -155,19 +415,19 @@ mod tests {
     use super::*;
 
     fn run_part1(input: &str) -> usize {
-        let parsed = input_generator(input);
-        solve_part1(&parsed)
+        let parsed = input_generator(input).unwrap();
+        solve_part1(&parsed).unwrap()
     }
 
     #[test]
     fn input_parsing() {
-        assert_eq!(input_generator("FBLR")[0], vec![
+        assert_eq!(input_generator("FBLR").unwrap()[0], vec![
             Direction::Front,
             Direction::Back,
             Direction::Left,
             Direction::Right,
         ]);
-        assert_eq!(input_generator("FBFBR")[0], vec![
+        assert_eq!(input_generator("FBFBR").unwrap()[0], vec![
             Direction::Front,
             Direction::Back,
             Direction::Front,
@@ -176,6 +436,15 @@ mod tests {
         ]);
     }
 
+    #[test]
+    fn invalid_passes_are_errors() {
+        assert_eq!(input_generator("FBFBBFFRLR\nFBFBXFFRLR"), Err(SeatError::InvalidLetter { position: 4, letter: 'X' }));
+
+        let passes = input_generator("FBFBBFFRLR\nFBFBBFFRL").unwrap();
+        assert_eq!(solve_part1(&passes), Err(SeatError::WrongLength { expected: 10, actual: 9 }));
+        assert_eq!(solve_part2(&passes), Err(SeatError::WrongLength { expected: 10, actual: 9 }));
+    }
+
     #[test]
     fn solve_provided_part1() {
         assert_eq!(run_part1("FBFBBFFRLR"), 357);
@@ -183,5 +452,135 @@ mod tests {
         assert_eq!(run_part1("BFFFBBFRRR"), 567);
         assert_eq!(run_part1("FFFBBBFRRR"), 119);
         assert_eq!(run_part1("BBFFBBFRLL"), 820);
+        assert_eq!(solve_part1(&[]), Err(SeatError::NoPasses));
+    }
+
+    #[test]
+    fn layout_decode() {
+        let layout = SeatLayout::default();
+
+        assert_eq!(layout.decode("FBFBBFFRLR"), Ok(Seat { row: 44, column: 5 }));
+        assert_eq!(layout.decode("BBFFBBFRLL"), Ok(Seat { row: 102, column: 4 }));
+
+        assert_eq!(layout.decode("FBFBBFFRL"), Err(SeatError::WrongLength { expected: 10, actual: 9 }));
+        assert_eq!(layout.decode("FBFBBFFRLX"), Err(SeatError::InvalidLetter { position: 9, letter: 'X' }));
+        assert_eq!(layout.decode("FBFBBFRRLR"), Err(SeatError::InvalidLetter { position: 6, letter: 'R' }));
+        assert_eq!(layout.decode("FBFBBFFRLF"), Err(SeatError::InvalidLetter { position: 9, letter: 'F' }));
+    }
+
+    #[test]
+    fn layout_encode() {
+        let layout = SeatLayout::default();
+
+        assert_eq!(layout.encode(&Seat { row: 44, column: 5 }), Ok("FBFBBFFRLR".to_owned()));
+        assert_eq!(layout.encode(&Seat { row: 128, column: 0 }), Err(SeatError::OutOfRange { row: 128, column: 0 }));
+
+        for id in 0..layout.seat_count() {
+            let seat = layout.seat_at(id);
+            let pass = layout.encode(&seat).unwrap();
+            assert_eq!(layout.decode(&pass), Ok(seat));
+            assert_eq!(layout.seat_id(&seat), id);
+        }
+    }
+
+    #[test]
+    fn layout_arbitrary_size() {
+        let layout = SeatLayout::new(100, 3).unwrap();
+        assert_eq!(layout.pass_len(), 9);
+
+        assert_eq!(layout.encode(&Seat { row: 99, column: 2 }), Ok("BBFFFBBRL".to_owned()));
+        assert_eq!(layout.decode("BBFFFBBRL"), Ok(Seat { row: 99, column: 2 }));
+        assert_eq!(layout.decode("BBFFBFFLL"), Err(SeatError::OutOfRange { row: 100, column: 0 }));
+        assert_eq!(layout.decode("FFFFFFFRR"), Err(SeatError::OutOfRange { row: 0, column: 3 }));
+
+        let single = SeatLayout::new(1, 1).unwrap();
+        assert_eq!(single.encode(&Seat { row: 0, column: 0 }), Ok("".to_owned()));
+
+        assert_eq!(SeatLayout::new(0, 8), Err(SeatError::EmptyLayout));
+    }
+
+    fn seat_map(layout: SeatLayout, ids: &[usize]) -> SeatMap {
+        let seats = ids.iter().map(|id| layout.seat_at(*id)).collect::<Vec<_>>();
+        SeatMap::new(layout, &seats).unwrap()
+    }
+
+    #[test]
+    fn vacancies_runs_rows_and_duplicates() {
+        let layout = SeatLayout::new(5, 4).unwrap();
+        let map = seat_map(layout, &[4, 5, 7, 8, 10, 11, 11, 12]);
+
+        let vacancies = map.vacancies();
+        assert_eq!(
+            vacancies.runs,
+            vec![
+                VacantRun { ids: 0..4, bounded: false },
+                VacantRun { ids: 6..7, bounded: true },
+                VacantRun { ids: 9..10, bounded: true },
+                VacantRun { ids: 13..20, bounded: false },
+            ]);
+        assert_eq!(vacancies.missing_front_rows, 0..1);
+        assert_eq!(vacancies.missing_back_rows, 4..5);
+        assert_eq!(vacancies.duplicate_ids, vec![11]);
+        assert_eq!(vacancies.isolated_ids().collect::<Vec<_>>(), vec![6, 9]);
+        assert_eq!(vacancies.vacant_ids().count(), 20 - 7);
+    }
+
+    #[test]
+    fn seat_map_rejects_outside_seats() {
+        let layout = SeatLayout::new(3, 2).unwrap();
+
+        assert_eq!(
+            SeatMap::new(layout, &[Seat { row: 0, column: 1 }, Seat { row: 3, column: 0 }]).map(|_| ()),
+            Err(SeatError::OutOfRange { row: 3, column: 0 }));
+        assert_eq!(
+            SeatMap::new(layout, &[Seat { row: 0, column: 2 }]).map(|_| ()),
+            Err(SeatError::OutOfRange { row: 0, column: 2 }));
+    }
+
+    #[test]
+    fn vacancies_empty_cabin() {
+        let vacancies = seat_map(SeatLayout::new(3, 2).unwrap(), &[]).vacancies();
+
+        assert_eq!(vacancies.runs, vec![VacantRun { ids: 0..6, bounded: false }]);
+        assert_eq!(vacancies.missing_front_rows, 0..3);
+        assert_eq!(vacancies.missing_back_rows, 3..3);
+    }
+
+    #[test]
+    fn solve_part2_requires_single_seat() {
+        let passes = |ids: &[usize]| {
+            let layout = SeatLayout::default();
+            ids.iter()
+                .map(|id| layout.encode(&layout.seat_at(*id)).unwrap())
+                .collect::<Vec<_>>()
+                .join("\n")
+        };
+
+        let solve = |ids: &[usize]| solve_part2(&input_generator(&passes(ids)).unwrap());
+
+        assert_eq!(solve(&[8, 9, 11, 12]), Ok(10));
+        assert_eq!(solve(&[8, 10, 12]), Err(SeatError::AmbiguousSeat(vec![9, 11])));
+        assert_eq!(solve(&[8, 9, 10]), Err(SeatError::AmbiguousSeat(vec![])));
+    }
+
+    #[test]
+    fn render_cabin() {
+        let layout = SeatLayout::new(3, 4).unwrap();
+        let map = seat_map(layout, &[4, 5, 5, 7, 8]);
+
+        assert_eq!(
+            map.render_text(&map.default_aisles()),
+            "  AB CD\n\
+             0 .. ..\n\
+             1 #! .#\n\
+             2 #. ..\n");
+
+        assert_eq!(
+            map.render_pgm(1),
+            "P2\n4 3\n255\n\
+             255 255 255 255\n\
+             0 128 255 0\n\
+             0 255 255 255\n");
+        assert_eq!(map.render_pgm(2).lines().nth(1), Some("8 6"));
     }
 }
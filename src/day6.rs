@@ -1,4 +1,5 @@
-use std::collections::HashSet;
+use std::fmt;
+use std::ops::{ BitAnd, BitOr, Sub };
 
 #[aoc_generator(day6)]
 pub fn input_generator(input: &str) -> Vec<Vec<Vec<char>>> {
@@ -19,44 +20,292 @@ pub fn input_generator(input: &str) -> Vec<Vec<Vec<char>>> {
     result
 }
 
-fn collect_set(input: &[char]) -> HashSet<char> {
-    input.iter().cloned().collect()
+const QUESTION_COUNT: usize = 26;
+
+fn question_bit(question: char) -> Option<u32> {
+    if question.is_ascii_lowercase() {
+        Some(1 << (question as u32 - 'a' as u32))
+    }
+    else {
+        None
+    }
+}
+
+fn question_at(idx: usize) -> char {
+    (b'a' + idx as u8) as char
+}
+
+// questions `a` through `z`, one bit each
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct AnswerSet(u32);
+
+impl AnswerSet {
+    pub fn empty() -> AnswerSet {
+        AnswerSet(0)
+    }
+
+    pub fn all() -> AnswerSet {
+        AnswerSet((1 << QUESTION_COUNT) - 1)
+    }
+
+    pub fn parse(answers: &[char]) -> Result<AnswerSet, String> {
+        answers.iter()
+            .try_fold(AnswerSet::empty(), |set, c| {
+                question_bit(*c)
+                    .map(|bit| AnswerSet(set.0 | bit))
+                    .ok_or_else(|| format!("Unknown question={:?}", c))
+            })
+    }
+
+    pub fn insert(&mut self, question: char) -> bool {
+        match question_bit(question) {
+            Some(bit) => {
+                let added = self.0 & bit == 0;
+                self.0 |= bit;
+                added
+            },
+            None => false,
+        }
+    }
+
+    pub fn contains(&self, question: char) -> bool {
+        question_bit(question).is_some_and(|bit| self.0 & bit != 0)
+    }
+
+    pub fn len(&self) -> usize {
+        self.0.count_ones() as usize
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0 == 0
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = char> {
+        let bits = self.0;
+        (0..QUESTION_COUNT)
+            .filter(move |idx| bits & (1 << idx) != 0)
+            .map(question_at)
+    }
+}
+
+impl BitOr for AnswerSet {
+    type Output = AnswerSet;
+
+    fn bitor(self, rhs: AnswerSet) -> AnswerSet {
+        AnswerSet(self.0 | rhs.0)
+    }
+}
+
+impl BitAnd for AnswerSet {
+    type Output = AnswerSet;
+
+    fn bitand(self, rhs: AnswerSet) -> AnswerSet {
+        AnswerSet(self.0 & rhs.0)
+    }
+}
+
+impl Sub for AnswerSet {
+    type Output = AnswerSet;
+
+    fn sub(self, rhs: AnswerSet) -> AnswerSet {
+        AnswerSet(self.0 & !rhs.0)
+    }
+}
+
+impl fmt::Display for AnswerSet {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&self.iter().collect::<String>())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct Histogram {
+    counts: [usize; QUESTION_COUNT],
+}
+
+impl Histogram {
+    pub fn count(&self, question: char) -> usize {
+        question_bit(question)
+            .map_or(0, |bit| self.counts[bit.trailing_zeros() as usize])
+    }
+
+    pub fn add(&mut self, answers: AnswerSet) {
+        for (idx, count) in self.counts.iter_mut().enumerate() {
+            if answers.0 & (1 << idx) != 0 {
+                *count += 1;
+            }
+        }
+    }
+
+    pub fn merge(&mut self, other: &Histogram) {
+        for (count, other) in self.counts.iter_mut().zip(other.counts.iter()) {
+            *count += other;
+        }
+    }
+
+    // questions answered by at least `lo` and at most `hi` members
+    fn select(&self, lo: usize, hi: usize) -> AnswerSet {
+        self.counts.iter()
+            .enumerate()
+            .filter(|(_, count)| **count >= lo && **count <= hi)
+            .fold(AnswerSet::empty(), |set, (idx, _)| AnswerSet(set.0 | 1 << idx))
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (char, usize)> + '_ {
+        self.counts.iter()
+            .enumerate()
+            .filter(|(_, count)| **count > 0)
+            .map(|(idx, count)| (question_at(idx), *count))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Outlier {
+    pub member: usize,
+    // answered by this member alone
+    pub unique: AnswerSet,
+    // answered by every other member, but not this one
+    pub missing: AnswerSet,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Group {
+    members: Vec<AnswerSet>,
+}
+
+impl Group {
+    pub fn parse(group: &[Vec<char>]) -> Result<Group, String> {
+        Ok(Group {
+            members: group.iter()
+                .map(|person| AnswerSet::parse(person))
+                .collect::<Result<Vec<_>, _>>()?,
+        })
+    }
+
+    pub fn members(&self) -> &[AnswerSet] {
+        &self.members
+    }
+
+    pub fn anyone(&self) -> AnswerSet {
+        self.members.iter().fold(AnswerSet::empty(), |acc, m| acc | *m)
+    }
+
+    pub fn everyone(&self) -> AnswerSet {
+        if self.members.is_empty() {
+            return AnswerSet::empty();
+        }
+
+        self.members.iter().fold(AnswerSet::all(), |acc, m| acc & *m)
+    }
+
+    pub fn at_least(&self, k: usize) -> AnswerSet {
+        self.histogram().select(std::cmp::max(k, 1), usize::MAX)
+    }
+
+    pub fn exactly(&self, k: usize) -> AnswerSet {
+        if k == 0 {
+            return AnswerSet::all() - self.anyone();
+        }
+
+        self.histogram().select(k, k)
+    }
+
+    pub fn exactly_one(&self) -> AnswerSet {
+        self.exactly(1)
+    }
+
+    pub fn histogram(&self) -> Histogram {
+        let mut histogram = Histogram::default();
+        for member in self.members.iter() {
+            histogram.add(*member);
+        }
+        histogram
+    }
+
+    pub fn outliers(&self) -> Vec<Outlier> {
+        if self.members.len() < 2 {
+            return Vec::new();
+        }
+
+        self.members.iter()
+            .enumerate()
+            .filter_map(|(member, answers)| {
+                let others = self.members.iter()
+                    .enumerate()
+                    .filter(|(i, _)| *i != member)
+                    .map(|(_, other)| *other);
+
+                let (others_any, others_all) = others
+                    .fold((AnswerSet::empty(), AnswerSet::all()), |(any, all), other| (any | other, all & other));
+
+                let outlier = Outlier {
+                    member,
+                    unique: *answers - others_any,
+                    missing: others_all - *answers,
+                };
+
+                if outlier.unique.is_empty() && outlier.missing.is_empty() { None } else { Some(outlier) }
+            })
+            .collect()
+    }
+}
+
+pub fn parse_groups(groups: &[Vec<Vec<char>>]) -> Result<Vec<Group>, String> {
+    groups.iter().map(|g| Group::parse(g)).collect()
+}
+
+pub fn survey_histogram(groups: &[Group]) -> Histogram {
+    let mut histogram = Histogram::default();
+    for group in groups {
+        histogram.merge(&group.histogram());
+    }
+    histogram
 }
 
 #[aoc(day6, part1)]
-pub fn solve_part1(groups: &[Vec<Vec<char>>]) -> usize {
-    groups.iter()
-        .map(|g| g.iter().cloned().flatten().collect::<HashSet<char>>())
-        .map(|common_answers| common_answers.len())
-        .sum()
+pub fn solve_part1(groups: &[Vec<Vec<char>>]) -> Result<usize, String> {
+    Ok(parse_groups(groups)?
+        .iter()
+        .map(|group| group.anyone().len())
+        .sum())
 }
 
 #[aoc(day6, part2)]
-pub fn solve_part2(groups: &[Vec<Vec<char>>]) -> usize {
-    groups.iter()
-        .map(|group| {
-            let group = group.iter()
-                .map(|v| collect_set(v))
-                .collect::<Vec<HashSet<char>>>();
-
-            // seed with first
-            group
-                .iter()
-                .skip(1)
-                .fold(group[0].clone(), |group_set, person| {
-                    group_set.intersection(&person)
-                        .cloned()
-                        .collect::<HashSet<char>>()
-                })
-        })
-        .map(|shared| shared.len())
-        .sum()
+pub fn solve_part2(groups: &[Vec<Vec<char>>]) -> Result<usize, String> {
+    Ok(parse_groups(groups)?
+        .iter()
+        .map(|group| group.everyone().len())
+        .sum())
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    const GIVEN_INPUT: &str = "abc\n\
+        \n\
+        a\n\
+        b\n\
+        c\n\
+        \n\
+        ab\n\
+        ac\n\
+        \n\
+        a\n\
+        a\n\
+        a\n\
+        a\n\
+        \n\
+        b";
+
+    fn set(answers: &str) -> AnswerSet {
+        AnswerSet::parse(&answers.chars().collect::<Vec<_>>()).unwrap()
+    }
+
+    fn group(members: &[&str]) -> Group {
+        Group::parse(&members.iter().map(|m| m.chars().collect()).collect::<Vec<_>>()).unwrap()
+    }
+
     #[test]
     fn input_parsing() {
         assert_eq!(
@@ -64,4 +313,77 @@ mod tests {
         assert_eq!(
             input_generator("ac\nb\n\n")[0], vec![vec!['a', 'c'], vec!['b']]);
     }
+
+    #[test]
+    fn solve_given_input() {
+        let groups = input_generator(GIVEN_INPUT);
+
+        assert_eq!(solve_part1(&groups), Ok(11));
+        assert_eq!(solve_part2(&groups), Ok(6));
+
+        let groups = input_generator("ab\n\nA");
+        assert_eq!(solve_part1(&groups), Err("Unknown question='A'".to_owned()));
+        assert_eq!(solve_part2(&groups), Err("Unknown question='A'".to_owned()));
+    }
+
+    #[test]
+    fn answer_set_operations() {
+        assert_eq!(AnswerSet::parse(&['a', 'c', 'a']), Ok(set("ac")));
+        assert_eq!(AnswerSet::parse(&['a', 'C']), Err("Unknown question='C'".to_owned()));
+
+        assert_eq!(set("abc") | set("cd"), set("abcd"));
+        assert_eq!(set("abc") & set("cd"), set("c"));
+        assert_eq!(set("abc") - set("cd"), set("ab"));
+        assert_eq!(set("zab").to_string(), "abz");
+        assert_eq!(set("abz").len(), 3);
+        assert!(set("abz").contains('z'));
+        assert!(!set("abz").contains('y'));
+        assert_eq!(AnswerSet::all().len(), 26);
+    }
+
+    #[test]
+    fn group_queries() {
+        let g = group(&["abc", "abd", "ae"]);
+
+        assert_eq!(g.anyone(), set("abcde"));
+        assert_eq!(g.everyone(), set("a"));
+        assert_eq!(g.at_least(2), set("ab"));
+        assert_eq!(g.exactly_one(), set("cde"));
+        assert_eq!(g.exactly(3), set("a"));
+        assert_eq!(g.exactly(0).len(), 21);
+
+        let histogram = g.histogram();
+        assert_eq!(histogram.count('a'), 3);
+        assert_eq!(histogram.count('b'), 2);
+        assert_eq!(histogram.count('z'), 0);
+        assert_eq!(
+            histogram.iter().collect::<Vec<_>>(),
+            vec![('a', 3), ('b', 2), ('c', 1), ('d', 1), ('e', 1)]);
+
+        assert_eq!(group(&[]).everyone(), AnswerSet::empty());
+    }
+
+    #[test]
+    fn group_outliers() {
+        let g = group(&["abc", "abc", "ab", "abcx"]);
+
+        assert_eq!(
+            g.outliers(),
+            vec![
+                Outlier { member: 2, unique: AnswerSet::empty(), missing: set("c") },
+                Outlier { member: 3, unique: set("x"), missing: AnswerSet::empty() },
+            ]);
+
+        assert!(group(&["abc"]).outliers().is_empty());
+    }
+
+    #[test]
+    fn survey_totals() {
+        let groups = parse_groups(&input_generator(GIVEN_INPUT)).unwrap();
+        let histogram = survey_histogram(&groups);
+
+        assert_eq!(histogram.count('a'), 8);
+        assert_eq!(histogram.count('b'), 4);
+        assert_eq!(histogram.count('c'), 3);
+    }
 }
@@ -1,5 +1,6 @@
-use std::collections::{ HashMap, HashSet, VecDeque };
-use std::cell::RefCell;
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt;
 use regex::Regex;
 
 /*
@@ -18,7 +19,7 @@ const CONTAIN_KEY: &str = "contain";
 
 const NO_RULES_KEY: &str = "no other bags.";
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct BagRule {
     color: String,
     count: usize,
@@ -44,7 +45,7 @@ impl BagRule {
     }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct Rule {
     color: String,
     rules: Vec<BagRule>,
@@ -70,117 +71,484 @@ impl Rule {
     }
 }
 
+impl fmt::Display for BagRule {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} {} {}", self.count, self.color, if self.count == 1 { "bag" } else { "bags" })
+    }
+}
+
+// writes the rule back out in the form `Rule::parse` reads
+impl fmt::Display for Rule {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} bags {} ", self.color, CONTAIN_KEY)?;
+
+        if self.rules.is_empty() {
+            return f.write_str(NO_RULES_KEY);
+        }
+
+        let rules = self.rules.iter()
+            .map(|r| r.to_string())
+            .collect::<Vec<_>>();
+        write!(f, "{}.", rules.join(", "))
+    }
+}
+
+pub fn write_rules(rules: &[Rule]) -> String {
+    rules.iter()
+        .map(|r| r.to_string())
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+// keeps every line, including repeated colours, so they can be reported by `BagGraph::validate`
 #[aoc_generator(day7)]
-pub fn input_generator(input: &str) -> HashMap<String, Rule> {
-    let result = input.lines()
-        .map(|line| Rule::parse(line))
-        .map(|rule| (rule.color.clone(), rule))
-        .collect::<HashMap<String, Rule>>();
-    result
-}
-
-fn compute_contained_in(rules: &HashMap<String, Rule>) -> HashMap::<String, HashSet<String>> {
-    let mut contained_in = HashMap::<String, RefCell<HashSet<String>>>::new();
-
-    for (key, rule) in rules {
-        rule.rules.iter().for_each(|r| {
-            match contained_in.get_mut(&r.color) {
-                Some(contained) => { contained.get_mut().insert(key.clone()); },
-                None => { 
-                    let mut new_set = HashSet::new();
-                    new_set.insert(key.clone());
-
-                    contained_in.insert(r.color.clone(), RefCell::new(new_set)); 
-                },
-            };
-        })
+pub fn input_generator(input: &str) -> Vec<Rule> {
+    input.lines()
+        .map(Rule::parse)
+        .collect()
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RuleError {
+    // colours along the cycle, the first colour is repeated at the end
+    Cycle(Vec<String>),
+    UndefinedColor { container: String, color: String },
+    DuplicateDefinition(String),
+    // the colour a query asks about appears in no rule
+    UnknownColor(String),
+    // the number of bags inside the colour does not fit in a `usize`
+    Overflow(String),
+}
+
+impl fmt::Display for RuleError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            RuleError::Cycle(path) => write!(f, "Cycle: {}", path.join(" -> ")),
+            RuleError::UndefinedColor { container, color } => {
+                write!(f, "Undefined color={:?} in rule for {:?}", color, container)
+            },
+            RuleError::DuplicateDefinition(color) => write!(f, "Duplicate rule for color={:?}", color),
+            RuleError::UnknownColor(color) => write!(f, "No rule mentions color={:?}", color),
+            RuleError::Overflow(color) => write!(f, "Bag count inside color={:?} overflows", color),
+        }
     }
+}
 
-    contained_in.into_iter()
-        .map(|(k, v)| (k, v.into_inner()))
-        .collect::<HashMap<String, HashSet<String>>>()
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RuleErrors(pub Vec<RuleError>);
+
+impl fmt::Display for RuleErrors {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let messages = self.0.iter()
+            .map(|e| e.to_string())
+            .collect::<Vec<_>>();
+        f.write_str(&messages.join("; "))
+    }
 }
 
-fn compute_valid_bags(rules: &HashMap<String, Rule>) -> HashSet<String> {
-    let contained_in = compute_contained_in(rules);
+impl Error for RuleErrors {}
 
-    let search_field = "shiny gold";
-    let mut valid_bags = HashSet::<String>::new();
+pub type ColorId = usize;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct BagGraph {
+    names: Vec<String>,
+    ids: HashMap<String, ColorId>,
+    contents: Vec<Vec<(ColorId, usize)>>,
+    containers: Vec<Vec<(ColorId, usize)>>,
+    defined: Vec<bool>,
+    duplicates: Vec<ColorId>,
+}
 
-    let mut next_q = vec![search_field.to_owned()];
+impl BagGraph {
+    pub fn new<'a, I>(rules: I) -> BagGraph
+        where
+            I: IntoIterator<Item = &'a Rule>,
+    {
+        let mut graph = BagGraph {
+            names: Vec::new(),
+            ids: HashMap::new(),
+            contents: Vec::new(),
+            containers: Vec::new(),
+            defined: Vec::new(),
+            duplicates: Vec::new(),
+        };
 
-    let mut visited = HashSet::<String>::new();
-    while let Some(color) = next_q.pop() {
+        for rule in rules {
+            let outer = graph.intern(&rule.color);
+            if graph.defined[outer] {
+                // only the first definition is used
+                graph.duplicates.push(outer);
+                continue;
+            }
 
-        if color != search_field {
-            valid_bags.insert(color.clone());
+            graph.defined[outer] = true;
+            for bag_rule in rule.rules.iter() {
+                let inner = graph.intern(&bag_rule.color);
+                graph.contents[outer].push((inner, bag_rule.count));
+                graph.containers[inner].push((outer, bag_rule.count));
+            }
         }
 
-        if !visited.insert(color.clone()) {
-            continue;
+        graph
+    }
+
+    fn intern(&mut self, color: &str) -> ColorId {
+        if let Some(id) = self.ids.get(color) {
+            return *id;
         }
 
-        if let Some(next_set) = contained_in.get(&color) {
-            for next_c in next_set.iter().cloned() {
-                next_q.push(next_c);
+        let id = self.names.len();
+        self.names.push(color.to_owned());
+        self.ids.insert(color.to_owned(), id);
+        self.contents.push(Vec::new());
+        self.containers.push(Vec::new());
+        self.defined.push(false);
+        id
+    }
+
+    // same as `new`, but rejects rule sets that `validate` finds problems in
+    pub fn checked<'a, I>(rules: I) -> Result<BagGraph, RuleErrors>
+        where
+            I: IntoIterator<Item = &'a Rule>,
+    {
+        let graph = BagGraph::new(rules);
+        graph.validate()?;
+        Ok(graph)
+    }
+
+    pub fn validate(&self) -> Result<(), RuleErrors> {
+        let mut errors = self.duplicates.iter()
+            .map(|id| RuleError::DuplicateDefinition(self.name(*id).to_owned()))
+            .collect::<Vec<_>>();
+
+        for (container, contents) in self.contents.iter().enumerate() {
+            for (inner, _) in contents.iter().filter(|(inner, _)| !self.defined[*inner]) {
+                errors.push(RuleError::UndefinedColor {
+                    container: self.name(container).to_owned(),
+                    color: self.name(*inner).to_owned(),
+                });
             }
         }
+
+        errors.extend(self.cycles().into_iter().map(RuleError::Cycle));
+
+        if errors.is_empty() { Ok(()) } else { Err(RuleErrors(errors)) }
     }
 
-    valid_bags
-}
+    // one cycle per back edge found by a depth-first walk over all bags
+    pub fn cycles(&self) -> Vec<Vec<String>> {
+        #[derive(Clone, Copy, PartialEq)]
+        enum Mark {
+            Unvisited,
+            Visiting,
+            Done,
+        }
 
-#[aoc(day7, part1)]
-pub fn solve_part1(bag_graph: &HashMap<String, Rule>) -> usize {
-    compute_valid_bags(bag_graph).len()
-}
+        let mut marks = vec![Mark::Unvisited; self.len()];
+        let mut cycles = Vec::new();
 
-#[aoc(day7, part2)]
-pub fn solve_part2(bag_graph: &HashMap<String, Rule>) -> usize {
-    let marked_color = "shiny gold";
+        for root in 0..self.len() {
+            if marks[root] != Mark::Unvisited {
+                continue;
+            }
+
+            marks[root] = Mark::Visiting;
+            let mut path = vec![(root, 0usize)];
+
+            while let Some((id, branch)) = path.last_mut() {
+                let id = *id;
+                match self.contents[id].get(*branch) {
+                    Some((inner, _)) => {
+                        *branch += 1;
+                        match marks[*inner] {
+                            Mark::Unvisited => {
+                                marks[*inner] = Mark::Visiting;
+                                path.push((*inner, 0));
+                            },
+                            Mark::Visiting => {
+                                let start = path.iter().position(|(p, _)| p == inner).unwrap();
+                                let cycle = path[start..].iter()
+                                    .map(|(p, _)| self.name(*p).to_owned())
+                                    .chain(std::iter::once(self.name(*inner).to_owned()))
+                                    .collect();
+                                cycles.push(cycle);
+                            },
+                            Mark::Done => {},
+                        }
+                    },
+                    None => {
+                        marks[id] = Mark::Done;
+                        path.pop();
+                    },
+                }
+            }
+        }
+
+        cycles
+    }
+
+    pub fn len(&self) -> usize {
+        self.names.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.names.is_empty()
+    }
+
+    pub fn id(&self, color: &str) -> Option<ColorId> {
+        self.ids.get(color).cloned()
+    }
+
+    pub fn name(&self, id: ColorId) -> &str {
+        &self.names[id]
+    }
+
+    // bags held directly inside `id`, with their counts
+    pub fn contents(&self, id: ColorId) -> &[(ColorId, usize)] {
+        &self.contents[id]
+    }
+
+    // bags directly holding `id`, with their counts
+    pub fn containers(&self, id: ColorId) -> &[(ColorId, usize)] {
+        &self.containers[id]
+    }
+
+    fn names_of(&self, ids: impl Iterator<Item = ColorId>) -> Vec<&str> {
+        let mut names = ids.map(|id| self.name(id)).collect::<Vec<_>>();
+        names.sort_unstable();
+        names
+    }
 
-    let mut memoized = bag_graph.iter()
-        .filter(|(_, r)| r.rules.is_empty())
-        .map(|(k, _)| (k.clone(), 1 as usize))
-        .collect::<HashMap<String, usize>>();
+    // every bag that eventually holds `color`
+    pub fn containers_of(&self, color: &str) -> Option<Vec<&str>> {
+        let start = self.id(color)?;
+
+        let mut visited = vec![false; self.len()];
+        let mut next_q = vec![start];
+        while let Some(id) = next_q.pop() {
+            for (container, _) in self.containers[id].iter() {
+                if !visited[*container] {
+                    visited[*container] = true;
+                    next_q.push(*container);
+                }
+            }
+        }
 
-    let mut q = VecDeque::<&str>::new();
-    q.push_back(marked_color);
+        Some(self.names_of((0..self.len()).filter(|id| visited[*id])))
+    }
 
-    while let Some(color) = q.pop_front() {
-        if memoized.contains_key(color) {
-            continue;
+    // folds `f` over the contents of every bag reachable from `start`, children first;
+    // `f` returns `None` when its result overflows
+    fn fold_contents<F>(&self, start: ColorId, mut f: F) -> Result<usize, RuleError>
+        where
+            F: FnMut(&[(ColorId, usize)], &[Option<usize>]) -> Option<usize>,
+    {
+        #[derive(Clone, Copy, PartialEq)]
+        enum Mark {
+            Unvisited,
+            Visiting,
+            Done,
         }
 
-        let within = bag_graph.get(color).unwrap();
-        let results = within.rules.iter()
-            .filter_map(|r| memoized.get(&r.color).map(|s| r.count * s))
-            .collect::<Vec<_>>();
-        
-        if results.len() == within.rules.len() {
-            let sum = results.into_iter().sum::<usize>();
-            memoized.insert(color.to_owned(), sum + 1);
-        }
-        else {
-            let uncounted_colors = within.rules.iter()
-                .filter_map(|r| {
-                    if memoized.contains_key(&r.color) {
-                        None 
-                    } else { 
-                        Some(&r.color) 
+        let mut marks = vec![Mark::Unvisited; self.len()];
+        let mut memoized = vec![None; self.len()];
+        // the bags currently being visited, outermost first
+        let mut path = Vec::new();
+
+        let mut stack = vec![start];
+        while let Some(&id) = stack.last() {
+            match marks[id] {
+                Mark::Unvisited => {
+                    marks[id] = Mark::Visiting;
+                    path.push(id);
+                    for (inner, _) in self.contents[id].iter() {
+                        match marks[*inner] {
+                            Mark::Unvisited => stack.push(*inner),
+                            Mark::Visiting => {
+                                let from = path.iter().position(|p| p == inner).unwrap();
+                                let cycle = path[from..].iter().chain(Some(inner))
+                                    .map(|id| self.name(*id).to_owned())
+                                    .collect();
+                                return Err(RuleError::Cycle(cycle));
+                            },
+                            Mark::Done => {},
+                        }
                     }
-                })
-                .collect::<Vec<_>>();
+                },
+                Mark::Visiting => {
+                    stack.pop();
+                    path.pop();
+                    marks[id] = Mark::Done;
+                    let value = f(&self.contents[id], &memoized)
+                        .ok_or_else(|| RuleError::Overflow(self.name(id).to_owned()))?;
+                    memoized[id] = Some(value);
+                },
+                Mark::Done => { stack.pop(); },
+            }
+        }
+
+        Ok(memoized[start].unwrap())
+    }
+
+    fn known_id(&self, color: &str) -> Result<ColorId, RuleError> {
+        self.id(color).ok_or_else(|| RuleError::UnknownColor(color.to_owned()))
+    }
+
+    // number of bags inside one `color` bag
+    pub fn total_contents(&self, color: &str) -> Result<usize, RuleError> {
+        let start = self.known_id(color)?;
+
+        self.fold_contents(start, |contents, memoized| {
+            contents.iter().try_fold(0usize, |total, (inner, count)| {
+                memoized[*inner].unwrap()
+                    .checked_add(1)
+                    .and_then(|bags| bags.checked_mul(*count))
+                    .and_then(|bags| total.checked_add(bags))
+            })
+        })
+    }
 
-            for uncounted_color in uncounted_colors {
-                q.push_back(uncounted_color);
+    // deepest level of nesting inside `color`, 0 when it holds nothing
+    pub fn max_depth(&self, color: &str) -> Result<usize, RuleError> {
+        let start = self.known_id(color)?;
+
+        self.fold_contents(start, |contents, memoized| {
+            Some(contents.iter()
+                .map(|(inner, _)| 1 + memoized[*inner].unwrap())
+                .max()
+                .unwrap_or(0))
+        })
+    }
+
+    // every chain of bags from `from` down to `to`, both included
+    pub fn paths(&self, from: &str, to: &str) -> Option<Vec<Vec<&str>>> {
+        let from = self.id(from)?;
+        let to = self.id(to)?;
+
+        // only descend into bags that can reach `to`
+        let mut reaches = vec![false; self.len()];
+        reaches[to] = true;
+        let mut next_q = vec![to];
+        while let Some(id) = next_q.pop() {
+            for (container, _) in self.containers[id].iter() {
+                if !reaches[*container] {
+                    reaches[*container] = true;
+                    next_q.push(*container);
+                }
             }
+        }
 
-            q.push_back(color);
+        let mut paths = Vec::new();
+        if !reaches[from] {
+            return Some(paths);
         }
+
+        let mut on_path = vec![false; self.len()];
+        let mut path = vec![from];
+        let mut branches = vec![0usize];
+        on_path[from] = true;
+
+        while let Some(&id) = path.last() {
+            if id == to {
+                paths.push(path.iter().map(|id| self.name(*id)).collect::<Vec<_>>());
+            }
+
+            let branch = branches.last_mut().unwrap();
+            let next = if id == to { None } else {
+                self.contents[id][*branch..].iter()
+                    .position(|(inner, _)| reaches[*inner] && !on_path[*inner])
+                    .map(|offset| *branch + offset)
+            };
+
+            match next {
+                Some(idx) => {
+                    *branch = idx + 1;
+                    let inner = self.contents[id][idx].0;
+                    on_path[inner] = true;
+                    path.push(inner);
+                    branches.push(0);
+                },
+                None => {
+                    on_path[id] = false;
+                    path.pop();
+                    branches.pop();
+                },
+            }
+        }
+
+        paths.sort_unstable();
+        Some(paths)
     }
 
-    *memoized.get(marked_color).unwrap() - 1
+    fn write_dot(&self, included: &[bool]) -> String {
+        fn quoted(name: &str) -> String {
+            format!("\"{}\"", name.replace('\\', "\\\\").replace('"', "\\\""))
+        }
+
+        let mut out = String::from("digraph bags {\n");
+        for id in (0..self.len()).filter(|id| included[*id]) {
+            out += &format!("    {};\n", quoted(self.name(id)));
+        }
+
+        for id in (0..self.len()).filter(|id| included[*id]) {
+            for (inner, count) in self.contents[id].iter() {
+                out += &format!(
+                    "    {} -> {} [label=\"{}\", weight={}];\n",
+                    quoted(self.name(id)),
+                    quoted(self.name(*inner)),
+                    count,
+                    count);
+            }
+        }
+
+        out += "}\n";
+        out
+    }
+
+    // Graphviz DOT of the whole rule set, edges labelled with the bag counts
+    pub fn to_dot(&self) -> String {
+        self.write_dot(&vec![true; self.len()])
+    }
+
+    // only `color` and the bags it can hold
+    pub fn to_dot_from(&self, color: &str) -> Option<String> {
+        let start = self.id(color)?;
+
+        let mut included = vec![false; self.len()];
+        included[start] = true;
+        let mut next_q = vec![start];
+        while let Some(id) = next_q.pop() {
+            for (inner, _) in self.contents[id].iter() {
+                if !included[*inner] {
+                    included[*inner] = true;
+                    next_q.push(*inner);
+                }
+            }
+        }
+
+        Some(self.write_dot(&included))
+    }
+}
+
+const TARGET_COLOR: &str = "shiny gold";
+
+fn unknown_target() -> RuleErrors {
+    RuleErrors(vec![RuleError::UnknownColor(TARGET_COLOR.to_owned())])
+}
+
+#[aoc(day7, part1)]
+pub fn solve_part1(rules: &[Rule]) -> Result<usize, RuleErrors> {
+    let graph = BagGraph::checked(rules)?;
+    graph.containers_of(TARGET_COLOR)
+        .map(|containers| containers.len())
+        .ok_or_else(unknown_target)
+}
+
+#[aoc(day7, part2)]
+pub fn solve_part2(rules: &[Rule]) -> Result<usize, RuleErrors> {
+    let graph = BagGraph::checked(rules)?;
+    graph.total_contents(TARGET_COLOR).map_err(|e| RuleErrors(vec![e]))
 }
 
 #[cfg(test)]
@@ -201,14 +569,12 @@ mod tests {
 
 
         let rules = input_generator(input);
-        let valid_bags = compute_valid_bags(&rules);
+        let graph = BagGraph::new(&rules);
+        let valid_bags = graph.containers_of("shiny gold").unwrap();
 
         assert_eq!(
             valid_bags, 
-            vec!["bright white", "light red", "dark orange", "muted yellow"].iter()
-                .cloned()
-                .map(|s| s.to_owned())
-                .collect::<HashSet<String>>());
+            vec!["bright white", "dark orange", "light red", "muted yellow"]);
     }
 
     #[test]
@@ -222,6 +588,184 @@ mod tests {
         dark violet bags contain no other bags.";
 
         let rules = input_generator(input);
-        assert_eq!(solve_part2(&rules), 126);
+        assert_eq!(solve_part2(&rules), Ok(126));
+    }
+
+    const GIVEN_INPUT_1: &str = "light red bags contain 1 bright white bag, 2 muted yellow bags.\n\
+        dark orange bags contain 3 bright white bags, 4 muted yellow bags.\n\
+        bright white bags contain 1 shiny gold bag.\n\
+        muted yellow bags contain 2 shiny gold bags, 9 faded blue bags.\n\
+        shiny gold bags contain 1 dark olive bag, 2 vibrant plum bags.\n\
+        dark olive bags contain 3 faded blue bags, 4 dotted black bags.\n\
+        vibrant plum bags contain 5 faded blue bags, 6 dotted black bags.\n\
+        faded blue bags contain no other bags.\n\
+        dotted black bags contain no other bags.";
+
+    #[test]
+    fn graph_queries_any_color() {
+        let rules = input_generator(GIVEN_INPUT_1);
+        let graph = BagGraph::new(&rules);
+
+        assert_eq!(graph.len(), 9);
+        assert_eq!(graph.containers_of("light red"), Some(vec![]));
+        assert_eq!(graph.containers_of("dark olive"), Some(vec!["bright white", "dark orange", "light red", "muted yellow", "shiny gold"]));
+        assert_eq!(graph.containers_of("plaid purple"), None);
+
+        assert_eq!(graph.total_contents("shiny gold"), Ok(32));
+        assert_eq!(graph.total_contents("dark olive"), Ok(7));
+        assert_eq!(graph.total_contents("faded blue"), Ok(0));
+        assert_eq!(graph.total_contents("plaid purple"), Err(RuleError::UnknownColor("plaid purple".to_owned())));
+
+        assert_eq!(graph.max_depth("light red"), Ok(4));
+        assert_eq!(graph.max_depth("shiny gold"), Ok(2));
+        assert_eq!(graph.max_depth("dotted black"), Ok(0));
+        assert_eq!(graph.max_depth("plaid purple"), Err(RuleError::UnknownColor("plaid purple".to_owned())));
+    }
+
+    #[test]
+    fn graph_contents_errors() {
+        let rules = input_generator(
+            "shiny gold bags contain 2 dark red bags.\n\
+             dark red bags contain 2 dark orange bags.\n\
+             dark orange bags contain 1 shiny gold bag.");
+        let graph = BagGraph::new(&rules);
+
+        let cycle = RuleError::Cycle(vec!["dark red".to_owned(), "dark orange".to_owned(), "shiny gold".to_owned(), "dark red".to_owned()]);
+        assert_eq!(graph.total_contents("dark red"), Err(cycle.clone()));
+        assert_eq!(graph.max_depth("dark red"), Err(cycle));
+
+        // over 100000 ** 4 bags inside shiny gold
+        let rules = input_generator(
+            "shiny gold bags contain 100000 dark red bags.\n\
+             dark red bags contain 100000 dark orange bags.\n\
+             dark orange bags contain 100000 faded blue bags.\n\
+             faded blue bags contain 100000 dotted black bags.\n\
+             dotted black bags contain no other bags.");
+        let graph = BagGraph::new(&rules);
+
+        assert_eq!(graph.total_contents("dark red"), Ok(1_000_010_000_100_000));
+        assert_eq!(graph.total_contents("shiny gold"), Err(RuleError::Overflow("shiny gold".to_owned())));
+        assert_eq!(graph.max_depth("shiny gold"), Ok(4));
+        assert_eq!(solve_part2(&rules).unwrap_err().to_string(), "Bag count inside color=\"shiny gold\" overflows");
+    }
+
+    #[test]
+    fn graph_paths() {
+        let rules = input_generator(GIVEN_INPUT_1);
+        let graph = BagGraph::new(&rules);
+
+        assert_eq!(
+            graph.paths("light red", "shiny gold"),
+            Some(vec![
+                vec!["light red", "bright white", "shiny gold"],
+                vec!["light red", "muted yellow", "shiny gold"],
+            ]));
+        assert_eq!(graph.paths("shiny gold", "light red"), Some(vec![]));
+        assert_eq!(graph.paths("faded blue", "faded blue"), Some(vec![vec!["faded blue"]]));
+        assert_eq!(graph.paths("muted yellow", "faded blue").unwrap().len(), 3);
+    }
+
+    #[test]
+    fn validate_cycles() {
+        let rules = input_generator(
+            "shiny gold bags contain 2 dark red bags.\n\
+             dark red bags contain 2 dark orange bags, 1 faded blue bag.\n\
+             dark orange bags contain 1 shiny gold bag.\n\
+             faded blue bags contain 3 faded blue bags.");
+
+        assert_eq!(
+            BagGraph::new(&rules).cycles(),
+            vec![
+                vec!["shiny gold", "dark red", "dark orange", "shiny gold"],
+                vec!["faded blue", "faded blue"],
+            ]);
+
+        let error = solve_part2(&rules).unwrap_err();
+        assert_eq!(error.0.len(), 2);
+        assert_eq!(
+            error.0[0].to_string(),
+            "Cycle: shiny gold -> dark red -> dark orange -> shiny gold");
+    }
+
+    #[test]
+    fn validate_undefined_and_duplicates() {
+        let rules = input_generator(
+            "shiny gold bags contain 2 dark red bags, 1 plaid purple bag.\n\
+             dark red bags contain no other bags.\n\
+             dark red bags contain 1 shiny gold bag.");
+
+        assert_eq!(
+            solve_part1(&rules),
+            Err(RuleErrors(vec![
+                RuleError::DuplicateDefinition("dark red".to_owned()),
+                RuleError::UndefinedColor { container: "shiny gold".to_owned(), color: "plaid purple".to_owned() },
+            ])));
+
+        // the duplicate is ignored, so it does not introduce a cycle
+        assert!(BagGraph::new(&rules).cycles().is_empty());
+
+        let rules = input_generator("dark red bags contain 2 faded blue bags.\nfaded blue bags contain no other bags.");
+        let unknown = Err(RuleErrors(vec![RuleError::UnknownColor("shiny gold".to_owned())]));
+        assert_eq!(solve_part1(&rules), unknown);
+        assert_eq!(solve_part2(&rules), unknown);
+    }
+
+    #[test]
+    fn validate_given_input() {
+        let rules = input_generator(GIVEN_INPUT_1);
+
+        assert_eq!(BagGraph::new(&rules).validate(), Ok(()));
+        assert_eq!(solve_part1(&rules), Ok(4));
+        assert_eq!(solve_part2(&rules), Ok(32));
+    }
+
+    #[test]
+    fn rule_round_trip() {
+        let rules = input_generator(GIVEN_INPUT_1);
+        let printed = write_rules(&rules);
+
+        assert_eq!(printed, GIVEN_INPUT_1);
+        assert_eq!(input_generator(&printed), rules);
+    }
+
+    #[test]
+    fn rule_round_trip_singular() {
+        let rule = Rule {
+            color: "pale teal".to_owned(),
+            rules: vec![
+                BagRule { color: "dim gray".to_owned(), count: 1 },
+                BagRule { color: "light lime".to_owned(), count: 12 },
+            ],
+        };
+
+        assert_eq!(rule.to_string(), "pale teal bags contain 1 dim gray bag, 12 light lime bags.");
+        assert_eq!(Rule::parse(&rule.to_string()), rule);
+    }
+
+    #[test]
+    fn dot_export() {
+        let rules = input_generator(
+            "shiny gold bags contain 1 dark olive bag, 2 vibrant plum bags.\n\
+             light red bags contain 3 shiny gold bags.\n\
+             dark olive bags contain no other bags.\n\
+             vibrant plum bags contain no other bags.");
+        let graph = BagGraph::new(&rules);
+
+        assert_eq!(
+            graph.to_dot(),
+            "digraph bags {\n\
+            \x20   \"shiny gold\";\n\
+            \x20   \"dark olive\";\n\
+            \x20   \"vibrant plum\";\n\
+            \x20   \"light red\";\n\
+            \x20   \"shiny gold\" -> \"dark olive\" [label=\"1\", weight=1];\n\
+            \x20   \"shiny gold\" -> \"vibrant plum\" [label=\"2\", weight=2];\n\
+            \x20   \"light red\" -> \"shiny gold\" [label=\"3\", weight=3];\n\
+             }\n");
+
+        let sub = graph.to_dot_from("shiny gold").unwrap();
+        assert!(!sub.contains("light red"));
+        assert!(sub.contains("\"shiny gold\" -> \"vibrant plum\""));
+        assert_eq!(graph.to_dot_from("plaid purple"), None);
     }
 }
@@ -1,3 +1,7 @@
+use std::collections::{ BTreeSet, HashMap, VecDeque };
+use std::error::Error;
+use std::fmt;
+use std::io::{ self, BufRead, Write };
 use std::num::ParseIntError;
 
 #[derive(Debug, Clone, PartialEq)]
@@ -7,37 +11,10 @@ pub enum Instruction {
     JMP(i32),
 }
 
-#[derive(Debug, Clone)]
-enum ParseError {
-    MalformedInput(String),
-    ArgumentParseError(ParseIntError),
-}
-
-fn invalid_input(input: &str) -> Result<Instruction, ParseError> {
-    Err(ParseError::MalformedInput(input.to_owned()))
-}
-
-impl Instruction {
-    fn parse(line: &str) -> Result<Instruction, ParseError> {
-        let parts = line.split_ascii_whitespace().collect::<Vec<_>>();
-        if parts.len() != 2 {
-            return invalid_input(line);
-        }
-
-        let value = parts[1].parse::<i32>().map_err(ParseError::ArgumentParseError)?;
-        match parts[0] {
-            "acc" => Ok(Instruction::ACC(value)),
-            "nop" => Ok(Instruction::NOP(value)),
-            "jmp" => Ok(Instruction::JMP(value)),
-            _ => invalid_input(line),
-        }
-    }
-}
-
-#[derive(Clone)]
-struct Cpu {
-    acc: i32,
-    ptr: i32,
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Cpu {
+    pub acc: i32,
+    pub ptr: i32,
 }
 
 impl Cpu {
@@ -54,7 +31,7 @@ impl Cpu {
         self.ptr += 1; // by default move one
     }
 
-    fn u_ptr(&self) -> Option<usize> {
+    pub fn u_ptr(&self) -> Option<usize> {
         if self.ptr >= 0 {
             Some(self.ptr as usize)
         }
@@ -64,6 +41,334 @@ impl Cpu {
     }
 }
 
+// an instruction in any dialect: an opcode from an `OpcodeTable` plus its arguments
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Op {
+    pub code: u8,
+    pub args: Vec<i32>,
+}
+
+// executes the op on the cpu, including moving `ptr`
+pub type Semantics = fn(&mut Cpu, &[i32]);
+
+#[derive(Clone)]
+pub struct OpcodeDef {
+    pub mnemonic: String,
+    pub code: u8,
+    pub arity: usize,
+    // the first argument is a relative jump target, shown resolved by the disassembler
+    pub jumps: bool,
+    pub semantics: Semantics,
+}
+
+impl fmt::Debug for OpcodeDef {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("OpcodeDef")
+            .field("mnemonic", &self.mnemonic)
+            .field("code", &self.code)
+            .field("arity", &self.arity)
+            .field("jumps", &self.jumps)
+            .finish()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum AsmError {
+    UnknownMnemonic { line: usize, mnemonic: String },
+    WrongArity { line: usize, expected: usize, found: usize },
+    BadArgument { line: usize, error: ParseIntError },
+    DuplicateMnemonic(String),
+    TableFull,
+    UnknownOpcode { offset: usize, code: u8 },
+    // executing an op the table doesn't define, `address` is the cpu's `ptr`
+    UndefinedOp { address: i32, code: u8 },
+    Truncated,
+    BadMagic,
+    // the op has no equivalent in `Instruction`
+    Unsupported(Op),
+}
+
+impl fmt::Display for AsmError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            AsmError::UnknownMnemonic { line, mnemonic } => write!(f, "line {}: Unknown mnemonic={:?}", line, mnemonic),
+            AsmError::WrongArity { line, expected, found } => {
+                write!(f, "line {}: Expected {} arguments, found {}", line, expected, found)
+            },
+            AsmError::BadArgument { line, error } => write!(f, "line {}: Bad argument: {}", line, error),
+            AsmError::DuplicateMnemonic(mnemonic) => write!(f, "Mnemonic={:?} is already registered", mnemonic),
+            AsmError::TableFull => write!(f, "No opcodes left"),
+            AsmError::UnknownOpcode { offset, code } => write!(f, "offset {}: Unknown opcode={}", offset, code),
+            AsmError::UndefinedOp { address, code } => write!(f, "address {}: Unknown opcode={}", address, code),
+            AsmError::Truncated => write!(f, "Unexpected end of program"),
+            AsmError::BadMagic => write!(f, "Not a boot code binary"),
+            AsmError::Unsupported(op) => write!(f, "No instruction for opcode={}", op.code),
+        }
+    }
+}
+
+impl Error for AsmError {}
+
+const BINARY_MAGIC: &[u8] = b"BOOT";
+
+fn write_varint(out: &mut Vec<u8>, value: i32) {
+    // zigzag so small negative offsets stay small
+    let mut v = ((value << 1) ^ (value >> 31)) as u32;
+    loop {
+        let byte = (v & 0x7f) as u8;
+        v >>= 7;
+        if v == 0 {
+            out.push(byte);
+            return;
+        }
+        out.push(byte | 0x80);
+    }
+}
+
+fn read_varint(bytes: &[u8], offset: &mut usize) -> Result<i32, AsmError> {
+    let mut v = 0u32;
+    for shift in (0..35).step_by(7) {
+        let byte = *bytes.get(*offset).ok_or(AsmError::Truncated)?;
+        *offset += 1;
+        v |= ((byte & 0x7f) as u32) << shift;
+        if byte & 0x80 == 0 {
+            return Ok(((v >> 1) as i32) ^ -((v & 1) as i32));
+        }
+    }
+
+    Err(AsmError::Truncated)
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct OpcodeTable {
+    defs: Vec<OpcodeDef>,
+    by_mnemonic: HashMap<String, u8>,
+}
+
+impl OpcodeTable {
+    pub fn new() -> OpcodeTable {
+        OpcodeTable::default()
+    }
+
+    // the `nop`, `acc` and `jmp` set that `Instruction` covers
+    pub fn standard() -> OpcodeTable {
+        fn core(cpu: &mut Cpu, ins: Instruction) {
+            cpu.execute(&ins);
+        }
+
+        let mut table = OpcodeTable::new();
+        table.register("nop", 1, false, |cpu, args| core(cpu, Instruction::NOP(args[0]))).unwrap();
+        table.register("acc", 1, false, |cpu, args| core(cpu, Instruction::ACC(args[0]))).unwrap();
+        table.register("jmp", 1, true, |cpu, args| core(cpu, Instruction::JMP(args[0]))).unwrap();
+        table
+    }
+
+    pub fn register(&mut self, mnemonic: &str, arity: usize, jumps: bool, semantics: Semantics) -> Result<u8, AsmError> {
+        if self.by_mnemonic.contains_key(mnemonic) {
+            return Err(AsmError::DuplicateMnemonic(mnemonic.to_owned()));
+        }
+
+        if self.defs.len() > u8::MAX as usize {
+            return Err(AsmError::TableFull);
+        }
+
+        let code = self.defs.len() as u8;
+        self.defs.push(OpcodeDef {
+            mnemonic: mnemonic.to_owned(),
+            code,
+            arity,
+            jumps: jumps && arity > 0,
+            semantics,
+        });
+        self.by_mnemonic.insert(mnemonic.to_owned(), code);
+
+        Ok(code)
+    }
+
+    pub fn get(&self, code: u8) -> Option<&OpcodeDef> {
+        self.defs.get(code as usize)
+    }
+
+    pub fn lookup(&self, mnemonic: &str) -> Option<&OpcodeDef> {
+        self.by_mnemonic.get(mnemonic).and_then(|code| self.get(*code))
+    }
+
+    fn assemble_line(&self, line_no: usize, line: &str) -> Result<Op, AsmError> {
+        let mut parts = line.split_ascii_whitespace();
+        let mnemonic = parts.next().unwrap_or("");
+
+        let def = self.lookup(mnemonic)
+            .ok_or_else(|| AsmError::UnknownMnemonic { line: line_no, mnemonic: mnemonic.to_owned() })?;
+
+        let args = parts
+            .map(|arg| arg.parse::<i32>().map_err(|error| AsmError::BadArgument { line: line_no, error }))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        if args.len() != def.arity {
+            return Err(AsmError::WrongArity { line: line_no, expected: def.arity, found: args.len() });
+        }
+
+        Ok(Op { code: def.code, args })
+    }
+
+    // one instruction per line, blank lines and `;` comments are skipped
+    pub fn assemble(&self, source: &str) -> Result<Vec<Op>, AsmError> {
+        source.lines()
+            .enumerate()
+            .map(|(i, line)| (i + 1, line.split(';').next().unwrap().trim()))
+            .filter(|(_, line)| !line.is_empty())
+            .map(|(line_no, line)| self.assemble_line(line_no, line))
+            .collect()
+    }
+
+    fn format_op(&self, op: &Op) -> String {
+        let args = op.args.iter()
+            .map(|arg| format!("{:+}", arg))
+            .collect::<Vec<_>>();
+
+        match self.get(op.code) {
+            Some(def) if args.is_empty() => def.mnemonic.clone(),
+            Some(def) => format!("{} {}", def.mnemonic, args.join(" ")),
+            None => format!(".op {} {}", op.code, args.join(" ")).trim_end().to_owned(),
+        }
+    }
+
+    // listing with addresses, jump targets resolved to absolute addresses
+    pub fn disassemble(&self, program: &[Op]) -> String {
+        let width = std::cmp::max(4, program.len().to_string().len());
+
+        program.iter()
+            .enumerate()
+            .map(|(address, op)| {
+                let text = self.format_op(op);
+
+                let target = match (self.get(op.code), op.args.first()) {
+                    (Some(def), Some(offset)) if def.jumps => Some(jump_target(address, *offset)),
+                    _ => None,
+                };
+
+                match target {
+                    None => format!("{:0width$}  {}\n", address, text, width = width),
+                    Some(Some(t)) if t == program.len() => {
+                        format!("{:0width$}  {:<12} ; -> end\n", address, text, width = width)
+                    },
+                    Some(Some(t)) if t < program.len() => {
+                        format!("{:0width$}  {:<12} ; -> {:0width$}\n", address, text, t, width = width)
+                    },
+                    Some(_) => format!("{:0width$}  {:<12} ; -> out of range\n", address, text, width = width),
+                }
+            })
+            .collect()
+    }
+
+    // `BOOT`, then per op: opcode byte followed by zigzag varint arguments
+    pub fn encode(&self, program: &[Op]) -> Vec<u8> {
+        let mut out = BINARY_MAGIC.to_vec();
+        for op in program {
+            out.push(op.code);
+            for arg in op.args.iter() {
+                write_varint(&mut out, *arg);
+            }
+        }
+        out
+    }
+
+    pub fn decode(&self, bytes: &[u8]) -> Result<Vec<Op>, AsmError> {
+        if !bytes.starts_with(BINARY_MAGIC) {
+            return Err(AsmError::BadMagic);
+        }
+
+        let mut program = Vec::new();
+        let mut offset = BINARY_MAGIC.len();
+        while offset < bytes.len() {
+            let code = bytes[offset];
+            let def = self.get(code).ok_or(AsmError::UnknownOpcode { offset, code })?;
+            offset += 1;
+
+            let args = (0..def.arity)
+                .map(|_| read_varint(bytes, &mut offset))
+                .collect::<Result<Vec<_>, _>>()?;
+            program.push(Op { code, args });
+        }
+
+        Ok(program)
+    }
+
+    pub fn execute(&self, cpu: &mut Cpu, op: &Op) -> Result<(), AsmError> {
+        let def = self.get(op.code).ok_or(AsmError::UndefinedOp { address: cpu.ptr, code: op.code })?;
+        (def.semantics)(cpu, &op.args);
+        Ok(())
+    }
+
+    // runs a program in this dialect until it terminates, loops or leaves the program
+    pub fn run(&self, program: &[Op]) -> Result<(Halt, Cpu), AsmError> {
+        let mut cpu = Cpu::default();
+        let mut visited = vec![false; program.len()];
+
+        loop {
+            let address = match cpu.u_ptr() {
+                Some(address) if address == program.len() => return Ok((Halt::Terminated, cpu)),
+                Some(address) if address < program.len() => address,
+                _ => return Ok((Halt::OutOfBounds(cpu.ptr), cpu)),
+            };
+
+            if visited[address] {
+                return Ok((Halt::Looped { entry: address }, cpu));
+            }
+            visited[address] = true;
+
+            self.execute(&mut cpu, &program[address])?;
+        }
+    }
+}
+
+lazy_static! {
+    static ref STANDARD_OPCODES: OpcodeTable = OpcodeTable::standard();
+}
+
+impl Instruction {
+    pub fn parse(line: &str) -> Result<Instruction, AsmError> {
+        let op = STANDARD_OPCODES.assemble_line(1, line.trim())?;
+        Instruction::from_op(&op)
+    }
+
+    pub fn to_op(&self) -> Op {
+        let mnemonic = match self {
+            Instruction::NOP(_) => "nop",
+            Instruction::ACC(_) => "acc",
+            Instruction::JMP(_) => "jmp",
+        };
+
+        let (Instruction::NOP(v) | Instruction::ACC(v) | Instruction::JMP(v)) = self;
+        Op {
+            code: STANDARD_OPCODES.lookup(mnemonic).unwrap().code,
+            args: vec![*v],
+        }
+    }
+
+    pub fn from_op(op: &Op) -> Result<Instruction, AsmError> {
+        let def = STANDARD_OPCODES.get(op.code)
+            .ok_or_else(|| AsmError::Unsupported(op.clone()))?;
+
+        match (&def.mnemonic[..], &op.args[..]) {
+            ("nop", [v]) => Ok(Instruction::NOP(*v)),
+            ("acc", [v]) => Ok(Instruction::ACC(*v)),
+            ("jmp", [v]) => Ok(Instruction::JMP(*v)),
+            _ => Err(AsmError::Unsupported(op.clone())),
+        }
+    }
+}
+
+impl fmt::Display for Instruction {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&STANDARD_OPCODES.format_op(&self.to_op()))
+    }
+}
+
+pub fn disassemble(program: &[Instruction]) -> String {
+    STANDARD_OPCODES.disassemble(&program.iter().map(Instruction::to_op).collect::<Vec<_>>())
+}
+
 #[aoc_generator(day8)]
 pub fn input_generator(input: &str) -> Vec<Instruction> {
     input.lines()
@@ -71,107 +376,626 @@ pub fn input_generator(input: &str) -> Vec<Instruction> {
         .collect()
 }
 
-enum ExecuteResult {
-    Looped(i32),
-    Completed(i32),
-    Failed,
+// like `input_generator`, but reports the failing line and allows `;` comments
+pub fn parse_program(source: &str) -> Result<Vec<Instruction>, AsmError> {
+    STANDARD_OPCODES.assemble(source)?
+        .iter()
+        .map(Instruction::from_op)
+        .collect()
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Watch {
+    AccEquals(i32),
+    AccAbove(i32),
+    AccBelow(i32),
+    AccChanged,
+}
+
+impl Watch {
+    fn triggered(&self, before: i32, after: i32) -> bool {
+        match self {
+            Watch::AccEquals(v) => after == *v && before != *v,
+            Watch::AccAbove(v) => after > *v && before <= *v,
+            Watch::AccBelow(v) => after < *v && before >= *v,
+            Watch::AccChanged => after != before,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct TraceEntry {
+    pub address: usize,
+    pub instruction: Instruction,
+    pub acc_before: i32,
+    pub acc_after: i32,
+}
+
+// keeps only the most recent `capacity` entries
+#[derive(Debug, Clone, PartialEq)]
+pub struct Trace {
+    capacity: usize,
+    entries: VecDeque<TraceEntry>,
+}
+
+impl Trace {
+    pub fn new(capacity: usize) -> Trace {
+        Trace {
+            capacity,
+            entries: VecDeque::with_capacity(capacity),
+        }
+    }
+
+    fn push(&mut self, entry: TraceEntry) {
+        if self.capacity == 0 {
+            return;
+        }
+
+        if self.entries.len() == self.capacity {
+            self.entries.pop_front();
+        }
+        self.entries.push_back(entry);
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &TraceEntry> {
+        self.entries.iter()
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    fn clear(&mut self) {
+        self.entries.clear();
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Halt {
+    Terminated,
+    // `entry` is the first instruction that was about to run twice
+    Looped { entry: usize },
+    OutOfBounds(i32),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Step {
+    Executed(usize),
+    Halted(Halt),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum StopReason {
+    Halted(Halt),
+    Breakpoint(usize),
+    // index into `Vm::watches`
+    Watch(usize),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct RunOutcome {
+    pub reason: StopReason,
+    pub acc: i32,
+    // every address executed since the last reset, in order
+    pub path: Vec<usize>,
 }
 
-fn run_cpu(program: &[Instruction], cpu: &mut Cpu, instruction_record: &mut [bool]) -> ExecuteResult {
-    while let Some(u_ptr) = cpu.u_ptr() {
-        if u_ptr == program.len() {
-            return ExecuteResult::Completed(cpu.acc)
+impl RunOutcome {
+    pub fn loop_entry(&self) -> Option<usize> {
+        match self.reason {
+            StopReason::Halted(Halt::Looped { entry }) => Some(entry),
+            _ => None,
+        }
+    }
+}
+
+const DEFAULT_TRACE_CAPACITY: usize = 64;
+
+#[derive(Debug, Clone)]
+pub struct Vm {
+    program: Vec<Instruction>,
+    cpu: Cpu,
+    visited: Vec<bool>,
+    path: Vec<usize>,
+    breakpoints: BTreeSet<usize>,
+    watches: Vec<Watch>,
+    trace: Trace,
+    // the breakpoint the last run stopped at, which the next run starts by executing
+    stopped_at: Option<usize>,
+}
+
+impl Vm {
+    pub fn new(program: Vec<Instruction>) -> Vm {
+        Vm::with_trace_capacity(program, DEFAULT_TRACE_CAPACITY)
+    }
+
+    pub fn with_trace_capacity(program: Vec<Instruction>, capacity: usize) -> Vm {
+        let visited = vec![false; program.len()];
+        Vm {
+            program,
+            cpu: Cpu::default(),
+            visited,
+            path: Vec::new(),
+            breakpoints: BTreeSet::new(),
+            watches: Vec::new(),
+            trace: Trace::new(capacity),
+            stopped_at: None,
+        }
+    }
+
+    pub fn cpu(&self) -> &Cpu {
+        &self.cpu
+    }
+
+    pub fn program(&self) -> &[Instruction] {
+        &self.program
+    }
+
+    pub fn trace(&self) -> &Trace {
+        &self.trace
+    }
+
+    pub fn path(&self) -> &[usize] {
+        &self.path
+    }
+
+    pub fn breakpoints(&self) -> impl Iterator<Item = &usize> {
+        self.breakpoints.iter()
+    }
+
+    pub fn add_breakpoint(&mut self, address: usize) -> bool {
+        self.breakpoints.insert(address)
+    }
+
+    pub fn remove_breakpoint(&mut self, address: usize) -> bool {
+        self.breakpoints.remove(&address)
+    }
+
+    pub fn watches(&self) -> &[Watch] {
+        &self.watches
+    }
+
+    pub fn add_watch(&mut self, watch: Watch) -> usize {
+        self.watches.push(watch);
+        self.watches.len() - 1
+    }
+
+    pub fn clear_watches(&mut self) {
+        self.watches.clear();
+    }
+
+    // replaces the instruction at `address`, returning the old one
+    pub fn patch(&mut self, address: usize, ins: Instruction) -> Option<Instruction> {
+        let slot = self.program.get_mut(address)?;
+        Some(std::mem::replace(slot, ins))
+    }
+
+    // restarts execution, keeping the program, breakpoints and watches
+    pub fn reset(&mut self) {
+        self.cpu = Cpu::default();
+        self.visited = vec![false; self.program.len()];
+        self.path.clear();
+        self.trace.clear();
+        self.stopped_at = None;
+    }
+
+    pub fn halted(&self) -> Option<Halt> {
+        match self.cpu.u_ptr() {
+            None => Some(Halt::OutOfBounds(self.cpu.ptr)),
+            Some(u_ptr) if u_ptr == self.program.len() => Some(Halt::Terminated),
+            Some(u_ptr) if u_ptr > self.program.len() => Some(Halt::OutOfBounds(self.cpu.ptr)),
+            Some(u_ptr) if self.visited[u_ptr] => Some(Halt::Looped { entry: u_ptr }),
+            Some(_) => None,
         }
-        
-        if instruction_record[u_ptr] {
-            return ExecuteResult::Looped(cpu.acc)
+    }
+
+    pub fn step(&mut self) -> Step {
+        if let Some(halt) = self.halted() {
+            return Step::Halted(halt);
         }
 
-        instruction_record[u_ptr] = true;
+        let address = self.cpu.ptr as usize;
+        let instruction = &self.program[address];
+        let acc_before = self.cpu.acc;
+        self.stopped_at = None;
+
+        self.visited[address] = true;
+        self.path.push(address);
+        self.cpu.execute(instruction);
 
-        cpu.execute(&program[u_ptr]);
+        self.trace.push(TraceEntry {
+            address,
+            instruction: instruction.clone(),
+            acc_before,
+            acc_after: self.cpu.acc,
+        });
+
+        Step::Executed(address)
     }
 
-    ExecuteResult::Failed
+    fn outcome(&self, reason: StopReason) -> RunOutcome {
+        RunOutcome {
+            reason,
+            acc: self.cpu.acc,
+            path: self.path.clone(),
+        }
+    }
+
+    // runs until the program halts, hits a breakpoint or triggers a watch
+    pub fn run(&mut self) -> RunOutcome {
+        let mut resumed = self.stopped_at.take();
+        loop {
+            if let Some(halt) = self.halted() {
+                return self.outcome(StopReason::Halted(halt));
+            }
+
+            // resuming from a breakpoint executes it instead of stopping there again
+            let address = self.cpu.ptr as usize;
+            if resumed.take() != Some(address) && self.breakpoints.contains(&address) {
+                self.stopped_at = Some(address);
+                return self.outcome(StopReason::Breakpoint(address));
+            }
+
+            let acc_before = self.cpu.acc;
+            self.step();
+
+            let watch = self.watches.iter()
+                .position(|w| w.triggered(acc_before, self.cpu.acc));
+            if let Some(idx) = watch {
+                return self.outcome(StopReason::Watch(idx));
+            }
+        }
+    }
 }
 
 #[aoc(day8, part1)]
 pub fn solve_part1(instructions: &[Instruction]) -> i32 {
-    let mut cpu = Cpu { acc: 0, ptr: 0 };
+    let outcome = Vm::with_trace_capacity(instructions.to_vec(), 0).run();
+
+    match outcome.reason {
+        StopReason::Halted(Halt::Looped { .. }) | StopReason::Halted(Halt::Terminated) => outcome.acc,
+        reason => panic!("Failed to execute? {:?}", reason),
+    }
+}
 
-    let mut instruction_record = vec![false; instructions.len()];
+fn jump_target(address: usize, offset: i32) -> Option<usize> {
+    let target = address as i64 + offset as i64;
+    if target >= 0 { Some(target as usize) } else { None }
+}
+
+// where control goes after `ins` at `address`, `None` when it leaves the program
+fn successor(ins: &Instruction, address: usize, len: usize) -> Option<usize> {
+    let target = match ins {
+        Instruction::JMP(offset) => jump_target(address, *offset)?,
+        Instruction::ACC(_) | Instruction::NOP(_) => address + 1,
+    };
+
+    if target <= len { Some(target) } else { None }
+}
 
-    match run_cpu(instructions, &mut cpu, &mut instruction_record) {
-        ExecuteResult::Looped(v) | ExecuteResult::Completed(v) => v,
-        ExecuteResult::Failed => panic!("Failed to execute?"),
+fn acc_delta(ins: &Instruction) -> i32 {
+    match ins {
+        Instruction::ACC(v) => *v,
+        Instruction::JMP(_) | Instruction::NOP(_) => 0,
     }
 }
 
-#[aoc(day8, part2)]
-pub fn solve_part2(instructions: &[Instruction]) -> i32 {
-    let cpu = Cpu { acc: 0, ptr: 0 };
+impl Instruction {
+    // the jmp/nop swap used to repair programs
+    pub fn flipped(&self) -> Option<Instruction> {
+        match self {
+            Instruction::JMP(v) => Some(Instruction::NOP(*v)),
+            Instruction::NOP(v) => Some(Instruction::JMP(*v)),
+            Instruction::ACC(_) => None,
+        }
+    }
+}
+
+// Control-flow graph of a program, address `len` being normal termination.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ControlFlow {
+    successors: Vec<Option<usize>>,
+    // accumulator change from an address until termination, when it terminates
+    acc_to_end: Vec<Option<i32>>,
+    // dfs entry and exit times in the tree of terminating addresses, rooted at `len`
+    entry: Vec<usize>,
+    exit: Vec<usize>,
+}
 
-    let instruction_record = vec![false; instructions.len()];
+impl ControlFlow {
+    pub fn new(program: &[Instruction]) -> ControlFlow {
+        let len = program.len();
 
-    fn descend(program: &[Instruction], instruction_record: &[bool], cpu: &Cpu, can_mutate: bool) -> Option<i32> {
-        let u_ptr = cpu.u_ptr()?;
+        let successors = program.iter()
+            .enumerate()
+            .map(|(address, ins)| successor(ins, address, len))
+            .collect::<Vec<_>>();
 
-        if u_ptr == program.len() {
-            // exit condition
-            return Some(cpu.acc);
+        let mut predecessors = vec![Vec::new(); len + 1];
+        for (address, next) in successors.iter().enumerate() {
+            if let Some(next) = next {
+                predecessors[*next].push(address);
+            }
         }
-    
-        if instruction_record[u_ptr] {
-            return None;
+
+        // every node has one successor, so walking back from the end visits each terminating address once
+        let mut acc_to_end = vec![None; len + 1];
+        let mut entry = vec![0; len + 1];
+        let mut exit = vec![0; len + 1];
+        acc_to_end[len] = Some(0);
+
+        let mut clock = 1;
+        let mut stack = vec![(len, 0)];
+        while let Some(&(address, child)) = stack.last() {
+            match predecessors[address].get(child) {
+                Some(&pred) => {
+                    stack.last_mut().unwrap().1 += 1;
+                    acc_to_end[pred] = Some(acc_delta(&program[pred]) + acc_to_end[address].unwrap());
+                    entry[pred] = clock;
+                    clock += 1;
+                    stack.push((pred, 0));
+                },
+                None => {
+                    exit[address] = clock;
+                    stack.pop();
+                },
+            }
+        }
+
+        ControlFlow {
+            successors,
+            acc_to_end,
+            entry,
+            exit,
         }
+    }
+
+    pub fn successor(&self, address: usize) -> Option<usize> {
+        self.successors.get(address).cloned().flatten()
+    }
+
+    pub fn terminates_from(&self, address: usize) -> bool {
+        self.acc_to_end.get(address).is_some_and(|acc| acc.is_some())
+    }
+
+    pub fn acc_to_end(&self, address: usize) -> Option<i32> {
+        self.acc_to_end.get(address).cloned().flatten()
+    }
+
+    // whether the run from `from` to the end goes through `via`, `from` included
+    pub fn passes_through(&self, from: usize, via: usize) -> bool {
+        self.terminates_from(from)
+            && self.terminates_from(via)
+            && self.entry[via] <= self.entry[from]
+            && self.exit[from] <= self.exit[via]
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Patch {
+    pub address: usize,
+    pub replacement: Instruction,
+    // accumulator once the patched program terminates
+    pub acc: i32,
+}
+
+// Every single jmp/nop flip that makes the program terminate, in execution order.
+pub fn find_repairs(program: &[Instruction]) -> Vec<Patch> {
+    let flow = ControlFlow::new(program);
+    let original = Vm::with_trace_capacity(program.to_vec(), 0).run();
+
+    let mut patches = Vec::new();
+    let mut acc = 0;
+    for address in original.path.iter().cloned() {
+        let ins = &program[address];
 
-        let ins = &program[u_ptr];
+        if let Some(replacement) = ins.flipped() {
+            // any earlier address leads on to `address`, so the rest of the run only needs to
+            // stay clear of the patch itself; a looping path never meets the terminating set
+            let acc_to_end = successor(&replacement, address, program.len())
+                .filter(|next| !flow.passes_through(*next, address))
+                .and_then(|next| flow.acc_to_end(next));
 
-        // if we already mutated the code in some way, we can't mutate anymore so just execute the program going forwards.
-        if !can_mutate {
-            return match run_cpu(program, &mut cpu.clone(), &mut instruction_record.to_vec()) {
-                ExecuteResult::Completed(v) => Some(v),
-                _ => None,
+            if let Some(acc_to_end) = acc_to_end {
+                patches.push(Patch { address, replacement, acc: acc + acc_to_end });
             }
         }
 
-        let mut instruction_record = instruction_record.to_vec();
-        instruction_record[u_ptr] = true;
+        acc += acc_delta(ins);
+    }
 
-        let mutated = match ins {
-            Instruction::ACC(_) => vec![(can_mutate, program.to_vec())],
-            Instruction::JMP(v) | Instruction::NOP(v) => {
-                // create two parallel executions w/ the instruction swapped for one
-                fn mutate_program(program: &[Instruction], loc: usize, ins: Instruction) -> Vec<Instruction> {
-                    let mut program = program.to_vec();
-                    program[loc] = ins;
-                    program
-                }
+    patches
+}
 
-                let with_jmp = mutate_program(program, u_ptr, Instruction::JMP(*v));
-                let with_nop = mutate_program(program, u_ptr, Instruction::NOP(*v));
+#[aoc(day8, part2)]
+pub fn solve_part2(instructions: &[Instruction]) -> Option<i32> {
+    find_repairs(instructions)
+        .first()
+        .map(|patch| patch.acc)
+}
 
-                vec![
-                    (*ins == Instruction::JMP(*v), with_jmp),
-                    (*ins == Instruction::NOP(*v), with_nop),
-                ]
-            },
+#[derive(Debug, Clone, PartialEq)]
+pub enum Command {
+    Step(usize),
+    Continue,
+    Break(usize),
+    Delete(usize),
+    Print(String),
+    Patch(usize, Instruction),
+    Reset,
+    Trace,
+    List,
+    Help,
+    Quit,
+}
+
+const REPL_HELP: &str = "\
+step [n]             run the next n instructions (default 1)
+continue             run until a halt or breakpoint
+break <addr>         stop before executing addr
+delete <addr>        remove the breakpoint at addr
+print acc|ptr        show a register
+patch <addr> <ins>   replace an instruction, e.g. `patch 7 nop -4`
+reset                restart, keeping patches and breakpoints
+trace                show the most recently executed instructions
+list                 disassemble the program
+quit                 leave\n";
+
+fn parse_address(arg: Option<&str>) -> Result<usize, String> {
+    let arg = arg.ok_or_else(|| "Missing address".to_owned())?;
+    arg.parse().map_err(|_| format!("Invalid address={:?}", arg))
+}
+
+impl Command {
+    pub fn parse(line: &str) -> Result<Command, String> {
+        let line = line.trim();
+        let (name, rest) = match line.find(char::is_whitespace) {
+            Some(idx) => (&line[..idx], line[idx..].trim()),
+            None => (line, ""),
         };
-        
-        mutated.into_iter()
-            .map(|(can_mutate, program)| {
-                let mut cpu = cpu.clone();
-                cpu.execute(&program[cpu.u_ptr()?]);
+        let arg = if rest.is_empty() { None } else { Some(rest) };
 
-                descend(&program, &instruction_record, &cpu, can_mutate)
-            })
-            .flatten()
-            .next()
+        match name {
+            "s" | "step" => match arg {
+                None => Ok(Command::Step(1)),
+                Some(n) => n.parse()
+                    .map(Command::Step)
+                    .map_err(|_| format!("Invalid count={:?}", n)),
+            },
+            "c" | "continue" => Ok(Command::Continue),
+            "b" | "break" => parse_address(arg).map(Command::Break),
+            "d" | "delete" => parse_address(arg).map(Command::Delete),
+            "p" | "print" => match arg {
+                Some(register @ "acc") | Some(register @ "ptr") => Ok(Command::Print(register.to_owned())),
+                other => Err(format!("Unknown register={:?}", other.unwrap_or(""))),
+            },
+            "patch" => {
+                let mut parts = rest.splitn(2, char::is_whitespace);
+                let address = parse_address(parts.next().filter(|s| !s.is_empty()))?;
+                let ins = Instruction::parse(parts.next().unwrap_or(""))
+                    .map_err(|e| e.to_string())?;
+                Ok(Command::Patch(address, ins))
+            },
+            "r" | "reset" => Ok(Command::Reset),
+            "t" | "trace" => Ok(Command::Trace),
+            "l" | "list" => Ok(Command::List),
+            "h" | "help" | "?" => Ok(Command::Help),
+            "q" | "quit" | "exit" => Ok(Command::Quit),
+            _ => Err(format!("Unknown command={:?}, try `help`", name)),
+        }
     }
+}
 
-    descend(instructions, &instruction_record, &cpu, true).unwrap()
+fn describe_halt(halt: Halt) -> String {
+    match halt {
+        Halt::Terminated => "terminated".to_owned(),
+        Halt::Looped { entry } => format!("loop detected, {:04} would run twice", entry),
+        Halt::OutOfBounds(ptr) => format!("jumped out of the program to {}", ptr),
+    }
 }
 
+fn describe_entry(entry: &TraceEntry) -> String {
+    format!("{:04}  {:<8} acc {} -> {}", entry.address, entry.instruction.to_string(), entry.acc_before, entry.acc_after)
+}
+
+// runs one command against `vm`, returning what to print
+pub fn apply_command(vm: &mut Vm, command: &Command) -> String {
+    match command {
+        Command::Step(n) => {
+            let mut out = String::new();
+            for _ in 0..*n {
+                let acc_before = vm.cpu().acc;
+                match vm.step() {
+                    Step::Executed(address) => out += &describe_entry(&TraceEntry {
+                        address,
+                        instruction: vm.program()[address].clone(),
+                        acc_before,
+                        acc_after: vm.cpu().acc,
+                    }),
+                    Step::Halted(halt) => {
+                        out += &describe_halt(halt);
+                        out.push('\n');
+                        break;
+                    },
+                }
+                out.push('\n');
+            }
+            out
+        },
+        Command::Continue => {
+            let outcome = vm.run();
+            let reason = match outcome.reason {
+                StopReason::Halted(halt) => describe_halt(halt),
+                StopReason::Breakpoint(address) => format!("breakpoint at {:04}", address),
+                StopReason::Watch(idx) => format!("watch {} triggered", idx),
+            };
+            format!("{}, acc = {}\n", reason, outcome.acc)
+        },
+        Command::Break(address) if *address >= vm.program().len() => {
+            format!("Address {} is outside the program\n", address)
+        },
+        Command::Break(address) => {
+            vm.add_breakpoint(*address);
+            format!("breakpoint at {:04}\n", address)
+        },
+        Command::Delete(address) => {
+            if vm.remove_breakpoint(*address) {
+                format!("removed breakpoint at {:04}\n", address)
+            }
+            else {
+                format!("No breakpoint at {:04}\n", address)
+            }
+        },
+        Command::Print(register) if register == "ptr" => format!("ptr = {}\n", vm.cpu().ptr),
+        Command::Print(_) => format!("acc = {}\n", vm.cpu().acc),
+        Command::Patch(address, ins) => match vm.patch(*address, ins.clone()) {
+            Some(old) => format!("{:04}  {} -> {}\n", address, old, ins),
+            None => format!("Address {} is outside the program\n", address),
+        },
+        Command::Reset => {
+            vm.reset();
+            "reset\n".to_owned()
+        },
+        Command::Trace => vm.trace()
+            .iter()
+            .map(|entry| describe_entry(entry) + "\n")
+            .collect(),
+        Command::List => disassemble(vm.program()),
+        Command::Help => REPL_HELP.to_owned(),
+        Command::Quit => String::new(),
+    }
+}
+
+// reads commands from `input` until it ends or `quit`, an empty line repeats the last command
+pub fn repl<R: BufRead, W: Write>(vm: &mut Vm, input: R, mut output: W) -> io::Result<()> {
+    let mut last = None;
+
+    write!(output, "(day8) ")?;
+    output.flush()?;
+    for line in input.lines() {
+        let line = line?;
+
+        let command = if line.trim().is_empty() { last.clone().ok_or_else(String::new) } else { Command::parse(&line) };
+        match command {
+            Ok(Command::Quit) => return Ok(()),
+            Ok(command) => {
+                output.write_all(apply_command(vm, &command).as_bytes())?;
+                last = Some(command);
+            },
+            Err(e) if e.is_empty() => (),
+            Err(e) => writeln!(output, "{}", e)?,
+        }
+
+        write!(output, "(day8) ")?;
+        output.flush()?;
+    }
+
+    writeln!(output)
+}
 
 #[cfg(test)]
 mod tests {
@@ -245,6 +1069,344 @@ mod tests {
 
         let acc_value = solve_part2(&program);
 
-        assert_eq!(8, acc_value); // provided
+        assert_eq!(Some(8), acc_value); // provided
+    }
+
+    const GIVEN_INPUT: &str = "nop +0\n\
+        acc +1\n\
+        jmp +4\n\
+        acc +3\n\
+        jmp -3\n\
+        acc -99\n\
+        acc +1\n\
+        jmp -4\n\
+        acc +6";
+
+    #[test]
+    fn vm_run_outcome() {
+        let mut vm = Vm::new(input_generator(GIVEN_INPUT));
+        let outcome = vm.run();
+
+        assert_eq!(outcome.reason, StopReason::Halted(Halt::Looped { entry: 1 }));
+        assert_eq!(outcome.loop_entry(), Some(1));
+        assert_eq!(outcome.acc, 5);
+        assert_eq!(outcome.path, vec![0, 1, 2, 6, 7, 3, 4]);
+
+        vm.patch(7, Instruction::NOP(-4));
+        vm.reset();
+        let outcome = vm.run();
+        assert_eq!(outcome.reason, StopReason::Halted(Halt::Terminated));
+        assert_eq!(outcome.acc, 8);
+        assert_eq!(outcome.loop_entry(), None);
+    }
+
+    #[test]
+    fn vm_single_step() {
+        let mut vm = Vm::new(input_generator(GIVEN_INPUT));
+
+        assert_eq!(vm.step(), Step::Executed(0));
+        assert_eq!(vm.step(), Step::Executed(1));
+        assert_eq!(vm.cpu(), &Cpu { acc: 1, ptr: 2 });
+        assert_eq!(vm.step(), Step::Executed(2));
+        assert_eq!(vm.cpu().ptr, 6);
+
+        let mut vm = Vm::new(vec![Instruction::JMP(-1)]);
+        assert_eq!(vm.step(), Step::Executed(0));
+        assert_eq!(vm.step(), Step::Halted(Halt::OutOfBounds(-1)));
+    }
+
+    #[test]
+    fn vm_breakpoints() {
+        let mut vm = Vm::new(input_generator(GIVEN_INPUT));
+        vm.add_breakpoint(3);
+        vm.add_breakpoint(6);
+
+        let outcome = vm.run();
+        assert_eq!(outcome.reason, StopReason::Breakpoint(6));
+        assert_eq!(outcome.path, vec![0, 1, 2]);
+
+        assert_eq!(vm.run().reason, StopReason::Breakpoint(3));
+        assert!(vm.remove_breakpoint(3));
+        assert_eq!(vm.run().reason, StopReason::Halted(Halt::Looped { entry: 1 }));
+
+        // a breakpoint on the first instruction stops a fresh run, and again after a reset
+        vm.add_breakpoint(0);
+        vm.reset();
+        assert_eq!(vm.run().reason, StopReason::Breakpoint(0));
+        assert_eq!(vm.run().reason, StopReason::Breakpoint(6));
+        vm.reset();
+        assert_eq!(vm.run().reason, StopReason::Breakpoint(0));
+    }
+
+    #[test]
+    fn vm_watches() {
+        let mut vm = Vm::new(input_generator(GIVEN_INPUT));
+        let above = vm.add_watch(Watch::AccAbove(1));
+
+        let outcome = vm.run();
+        assert_eq!(outcome.reason, StopReason::Watch(above));
+        assert_eq!(outcome.acc, 2);
+        assert_eq!(vm.cpu().ptr, 7);
+
+        vm.clear_watches();
+        vm.add_watch(Watch::AccChanged);
+        assert_eq!(vm.run().acc, 5);
+    }
+
+    #[test]
+    fn vm_trace_ring_buffer() {
+        let mut vm = Vm::with_trace_capacity(input_generator(GIVEN_INPUT), 3);
+        vm.run();
+
+        assert_eq!(vm.trace().len(), 3);
+        assert_eq!(
+            vm.trace().iter().map(|e| e.address).collect::<Vec<_>>(),
+            vec![7, 3, 4]);
+        assert_eq!(
+            vm.trace().iter().nth(1),
+            Some(&TraceEntry { address: 3, instruction: Instruction::ACC(3), acc_before: 2, acc_after: 5 }));
+
+        vm.reset();
+        assert!(vm.trace().is_empty());
+        assert!(vm.path().is_empty());
+    }
+
+    #[test]
+    fn control_flow_terminating_set() {
+        let flow = ControlFlow::new(&input_generator(GIVEN_INPUT));
+
+        assert_eq!(flow.successor(2), Some(6));
+        assert_eq!(flow.successor(9), None);
+        assert_eq!(
+            (0..=9).filter(|a| flow.terminates_from(*a)).collect::<Vec<_>>(),
+            vec![8, 9]);
+        assert_eq!(flow.acc_to_end(8), Some(6));
+        assert_eq!(flow.acc_to_end(0), None);
+    }
+
+    #[test]
+    fn repairs_all_patches() {
+        let program = input_generator(GIVEN_INPUT);
+
+        assert_eq!(
+            find_repairs(&program),
+            vec![Patch { address: 7, replacement: Instruction::NOP(-4), acc: 8 }]);
+
+        // both the nop at 1 and the jmp at 2 lead out of the loop
+        let program = input_generator(
+            "acc +1\n\
+             nop +3\n\
+             jmp +0\n\
+             acc +2\n\
+             acc +4");
+
+        assert_eq!(
+            find_repairs(&program),
+            vec![
+                Patch { address: 1, replacement: Instruction::JMP(3), acc: 5 },
+                Patch { address: 2, replacement: Instruction::NOP(0), acc: 7 },
+            ]);
+    }
+
+    #[test]
+    fn repairs_no_patch() {
+        let program = input_generator("jmp +0\njmp -1");
+        assert_eq!(find_repairs(&program), vec![]);
+        assert_eq!(solve_part2(&program), None);
+
+        // already terminating: flipping the nop would jump back into the path
+        let program = input_generator("acc +1\nnop -1\nacc +2");
+        assert_eq!(find_repairs(&program), vec![]);
+    }
+
+    #[test]
+    fn repairs_terminating_program() {
+        // flipping 0 skips ahead into a terminating run, flipping 2 jumps to 1 which leads back to 2
+        let program = input_generator(
+            "jmp +2\n\
+             acc +5\n\
+             nop -1\n\
+             acc +1");
+
+        let flow = ControlFlow::new(&program);
+        assert!(flow.passes_through(1, 2));
+        assert!(flow.passes_through(2, 2));
+        assert!(!flow.passes_through(1, 0));
+        assert!(!flow.passes_through(3, 2));
+
+        assert_eq!(
+            find_repairs(&program),
+            vec![Patch { address: 0, replacement: Instruction::NOP(2), acc: 6 }]);
+    }
+
+    #[test]
+    fn repairs_match_running_every_flip() {
+        let mut state = 7u64;
+        let mut random = |n: u64| {
+            state = state.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+            (state >> 33) % n
+        };
+
+        for _ in 0..500 {
+            let program = (0..10)
+                .map(|_| {
+                    let v = random(9) as i32 - 4;
+                    match random(3) {
+                        0 => Instruction::NOP(v),
+                        1 => Instruction::ACC(v),
+                        _ => Instruction::JMP(v),
+                    }
+                })
+                .collect::<Vec<_>>();
+
+            let path = Vm::new(program.clone()).run().path;
+            let expected = path.iter()
+                .filter_map(|address| {
+                    let replacement = program[*address].flipped()?;
+                    let mut vm = Vm::new(program.clone());
+                    vm.patch(*address, replacement.clone());
+
+                    let outcome = vm.run();
+                    if outcome.reason == StopReason::Halted(Halt::Terminated) {
+                        Some(Patch { address: *address, replacement, acc: outcome.acc })
+                    }
+                    else {
+                        None
+                    }
+                })
+                .collect::<Vec<_>>();
+
+            assert_eq!(find_repairs(&program), expected, "{:?}", program);
+        }
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn instruction_parsing() {
+        assert_eq!(Instruction::parse("jmp -4"), Ok(Instruction::JMP(-4)));
+        assert_eq!(Instruction::JMP(-4).to_string(), "jmp -4");
+        assert_eq!(Instruction::NOP(0).to_string(), "nop +0");
+        assert_eq!(
+            Instruction::parse("hlt +1"),
+            Err(AsmError::UnknownMnemonic { line: 1, mnemonic: "hlt".to_owned() }));
+        assert_eq!(
+            Instruction::parse("acc +1 +2"),
+            Err(AsmError::WrongArity { line: 1, expected: 1, found: 2 }));
+    }
+
+    #[test]
+    fn disassemble_resolves_jumps() {
+        let program = input_generator("nop +0\njmp +2\njmp -2\nacc +1\njmp +9");
+
+        assert_eq!(
+            disassemble(&program),
+            "0000  nop +0\n\
+             0001  jmp +2       ; -> 0003\n\
+             0002  jmp -2       ; -> 0000\n\
+             0003  acc +1\n\
+             0004  jmp +9       ; -> out of range\n");
+    }
+
+    #[test]
+    fn binary_round_trip() {
+        let table = OpcodeTable::standard();
+        let program = input_generator(GIVEN_INPUT).iter()
+            .map(Instruction::to_op)
+            .collect::<Vec<_>>();
+
+        let bytes = table.encode(&program);
+        assert_eq!(&bytes[..8], b"BOOT\x00\x00\x01\x02");
+        assert_eq!(table.decode(&bytes), Ok(program));
+
+        assert_eq!(table.decode(b"BOOT\x02"), Err(AsmError::Truncated));
+        assert_eq!(table.decode(b"BOOT\x07\x00"), Err(AsmError::UnknownOpcode { offset: 4, code: 7 }));
+        assert_eq!(table.decode(b"nop +0"), Err(AsmError::BadMagic));
+
+        let program = vec![Instruction::NOP(0).to_op(), Op { code: 7, args: vec![] }];
+        assert_eq!(table.run(&program), Err(AsmError::UndefinedOp { address: 1, code: 7 }));
+    }
+
+    #[test]
+    fn extended_dialect() {
+        let mut table = OpcodeTable::standard();
+        let mul = table.register("mul", 1, false, |cpu, args| {
+            cpu.acc *= args[0];
+            cpu.ptr += 1;
+        }).unwrap();
+        table.register("hlt", 0, false, |cpu, _| cpu.ptr = i32::MAX).unwrap();
+
+        assert_eq!(table.register("acc", 1, false, |_, _| ()), Err(AsmError::DuplicateMnemonic("acc".to_owned())));
+
+        let program = table.assemble(
+            "acc +3\n\
+             mul -5 ; negate and scale\n\
+             \n\
+             hlt").unwrap();
+
+        assert_eq!(program[1], Op { code: mul, args: vec![-5] });
+        assert_eq!(table.disassemble(&program), "0000  acc +3\n0001  mul -5\n0002  hlt\n");
+        assert_eq!(table.decode(&table.encode(&program)), Ok(program.clone()));
+
+        let (halt, cpu) = table.run(&program).unwrap();
+        assert_eq!(halt, Halt::OutOfBounds(i32::MAX));
+        assert_eq!(cpu.acc, -15);
+
+        assert_eq!(Instruction::from_op(&program[1]), Err(AsmError::Unsupported(program[1].clone())));
+        assert_eq!(
+            table.assemble("acc +1\nmul x"),
+            Err(AsmError::BadArgument { line: 2, error: "x".parse::<i32>().unwrap_err() }));
+    }
+
+    #[test]
+    fn repl_command_parsing() {
+        assert_eq!(Command::parse("step"), Ok(Command::Step(1)));
+        assert_eq!(Command::parse("  s 5 "), Ok(Command::Step(5)));
+        assert_eq!(Command::parse("break 12"), Ok(Command::Break(12)));
+        assert_eq!(Command::parse("print acc"), Ok(Command::Print("acc".to_owned())));
+        assert_eq!(Command::parse("patch 7 nop -4"), Ok(Command::Patch(7, Instruction::NOP(-4))));
+
+        assert_eq!(Command::parse("break"), Err("Missing address".to_owned()));
+        assert_eq!(Command::parse("break -1"), Err("Invalid address=\"-1\"".to_owned()));
+        assert_eq!(Command::parse("print sp"), Err("Unknown register=\"sp\"".to_owned()));
+        assert_eq!(Command::parse("patch 7 hlt"), Err("line 1: Unknown mnemonic=\"hlt\"".to_owned()));
+    }
+
+    #[test]
+    fn repl_session() {
+        let mut vm = Vm::new(input_generator(GIVEN_INPUT));
+        let input = "break 4\ncontinue\nprint acc\nstep\n\npatch 7 nop -4\nreset\ncontinue\nquit\nstep\n";
+
+        let mut output = Vec::new();
+        repl(&mut vm, input.as_bytes(), &mut output).unwrap();
+
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            "(day8) breakpoint at 0004\n\
+             (day8) breakpoint at 0004, acc = 5\n\
+             (day8) acc = 5\n\
+             (day8) 0004  jmp -3   acc 5 -> 5\n\
+             (day8) loop detected, 0001 would run twice\n\
+             (day8) 0007  jmp -4 -> nop -4\n\
+             (day8) reset\n\
+             (day8) terminated, acc = 8\n\
+             (day8) ");
+    }
+
+    #[test]
+    fn repl_step_without_trace() {
+        let mut vm = Vm::with_trace_capacity(input_generator(GIVEN_INPUT), 0);
+
+        assert_eq!(
+            apply_command(&mut vm, &Command::Step(2)),
+            "0000  nop +0   acc 0 -> 0\n\
+             0001  acc +1   acc 0 -> 1\n");
+        assert!(vm.trace().iter().next().is_none());
+    }
+
+    #[test]
+    fn program_parsing_errors() {
+        assert_eq!(parse_program("nop +0 ; start\nacc +1"), Ok(vec![Instruction::NOP(0), Instruction::ACC(1)]));
+        assert_eq!(
+            parse_program("nop +0\nacc\n").map_err(|e| e.to_string()),
+            Err("line 2: Expected 1 arguments, found 0".to_owned()));
+    }
+}
@@ -1,3 +1,6 @@
+use std::collections::{ HashMap, VecDeque };
+use std::ops::Range;
+
 #[aoc_generator(day9)]
 pub fn input_generator(input: &str) -> Vec<usize> {
     input.lines()
@@ -5,73 +8,314 @@ pub fn input_generator(input: &str) -> Vec<usize> {
         .collect()
 }
 
-fn find_sum(preamble: &[usize], n: &usize) -> bool {
-    for (i, left) in preamble.iter().enumerate().filter(|(_, l)| *l <= n) {
-        for right in preamble.iter().skip(i).filter(|r| *r <= n) {
-            if left + right == *n {
-                return true;
-            }
+const PREAMBLE_LEN: usize = 25;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Verdict {
+    Preamble,
+    Valid,
+    Invalid,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct InvalidNumber {
+    pub position: usize,
+    pub value: usize,
+}
+
+// sums that overflow can never match a number, so they are left out
+fn pair_sums(window: &VecDeque<usize>, value: usize) -> impl Iterator<Item = usize> + '_ {
+    window.iter()
+        .filter(move |other| **other != value)
+        .filter_map(move |other| value.checked_add(*other))
+}
+
+// sliding window over the last `preamble` numbers with a count of every pair sum in it;
+// a push adds and drops one number's sums, so it costs O(preamble) rather than O(1)
+#[derive(Debug, Clone)]
+pub struct XmasValidator {
+    preamble: usize,
+    window: VecDeque<usize>,
+    sums: HashMap<usize, usize>,
+    position: usize,
+}
+
+impl XmasValidator {
+    pub fn new(preamble: usize) -> XmasValidator {
+        XmasValidator {
+            preamble,
+            window: VecDeque::with_capacity(preamble),
+            sums: HashMap::new(),
+            position: 0,
         }
     }
 
-    false
-}
+    pub fn preamble(&self) -> usize {
+        self.preamble
+    }
 
-fn find_weak_value(encoded: &[usize], preamble_n: usize) -> Option<usize> {
-    let preamble_iter = (preamble_n..encoded.len())
-        .map(|i| &encoded[i - preamble_n..i]);
+    // position of the next number pushed
+    pub fn position(&self) -> usize {
+        self.position
+    }
 
-    for (preamble, n) in preamble_iter.zip(encoded.iter().skip(preamble_n)) {
-        if !find_sum(preamble, n) {
-            return Some(*n);
-        }       
+    // whether `value` is the sum of two different numbers in the window
+    pub fn is_valid(&self, value: usize) -> bool {
+        self.sums.get(&value).is_some_and(|count| *count > 0)
     }
 
-    None
+    pub fn push(&mut self, value: usize) -> Verdict {
+        let verdict = if self.position < self.preamble {
+            Verdict::Preamble
+        }
+        else if self.is_valid(value) {
+            Verdict::Valid
+        }
+        else {
+            Verdict::Invalid
+        };
+
+        if self.preamble > 0 {
+            if self.window.len() == self.preamble {
+                let old = self.window.pop_front().unwrap();
+                for sum in pair_sums(&self.window, old) {
+                    let count = self.sums.get_mut(&sum).unwrap();
+                    *count -= 1;
+                    if *count == 0 {
+                        self.sums.remove(&sum);
+                    }
+                }
+            }
+
+            for sum in pair_sums(&self.window, value) {
+                *self.sums.entry(sum).or_insert(0) += 1;
+            }
+            self.window.push_back(value);
+        }
+
+        self.position += 1;
+        verdict
+    }
+
+    // every invalid number after the preamble, in order
+    pub fn validate<I: IntoIterator<Item = usize>>(&mut self, numbers: I) -> Vec<InvalidNumber> {
+        numbers.into_iter()
+            .filter_map(|value| {
+                let position = self.position;
+                match self.push(value) {
+                    Verdict::Invalid => Some(InvalidNumber { position, value }),
+                    Verdict::Preamble | Verdict::Valid => None,
+                }
+            })
+            .collect()
+    }
+}
+
+pub fn find_invalid_numbers(encoded: &[usize], preamble: usize) -> Vec<InvalidNumber> {
+    XmasValidator::new(preamble).validate(encoded.iter().cloned())
+}
+
+fn find_weak_value(encoded: &[usize], preamble_n: usize) -> Option<usize> {
+    let mut validator = XmasValidator::new(preamble_n);
+    encoded.iter()
+        .find(|value| validator.push(**value) == Verdict::Invalid)
+        .cloned()
 }
 
 #[aoc(day9, part1)]
 pub fn solve_part1(encoded: &[usize]) -> usize {
-    find_weak_value(encoded, 25).unwrap()
+    find_weak_value(encoded, PREAMBLE_LEN).unwrap()
 }
 
-fn find_contiguous_weak_sum(weak_value: &usize, encoded: &[usize]) -> Option<(usize, usize)> {
-    let valid_slices = encoded.iter()
-        .take(encoded.len() - 1)
-        .enumerate()
-        .filter(|(_, v)| *v < weak_value)
-        .map(|(i, _)| &encoded[i..]);
-
-    for slice in valid_slices {
-        let mut current_sum = 0;
-        let mut min = usize::MAX;
-        let mut max = usize::MIN;
-        for p in slice.iter().cloned() {
-            current_sum += p;
-            min = std::cmp::min(min, p);
-            max = std::cmp::max(max, p);
-
-            if current_sum == *weak_value {
-                return Some((min, max))
-            }
+// the first run of at least two numbers adding up to `target`, with a two-pointer sweep
+pub fn find_contiguous_range(encoded: &[usize], target: usize) -> Option<Range<usize>> {
+    let mut start = 0;
+    let mut sum = 0usize;
+    for (end, value) in encoded.iter().enumerate() {
+        // drop numbers from the front until `value` fits, the sum is 0 once the range is empty
+        while start < end && sum.checked_add(*value).filter(|next| *next <= target).is_none() {
+            sum -= encoded[start];
+            start += 1;
+        }
+        sum += value;
+
+        if sum == target && end > start {
+            return Some(start..end + 1);
         }
     }
 
     None
 }
 
+fn find_contiguous_weak_sum(weak_value: &usize, encoded: &[usize]) -> Option<(usize, usize)> {
+    let range = &encoded[find_contiguous_range(encoded, *weak_value)?];
+    Some((*range.iter().min()?, *range.iter().max()?))
+}
+
 #[aoc(day9, part2)]
 pub fn solve_part2(encoded: &[usize]) -> usize {
-    let weak_value = find_weak_value(encoded, 25).unwrap();
+    let weak_value = find_weak_value(encoded, PREAMBLE_LEN).unwrap();
 
     let (min, max) = find_contiguous_weak_sum(&weak_value, encoded).unwrap();
     min + max
 }
 
+// xorshift64*, enough to make reproducible test data
+#[derive(Debug, Clone)]
+struct XorShift(u64);
+
+impl XorShift {
+    fn new(seed: u64) -> XorShift {
+        // the state must never be zero
+        XorShift(seed ^ 0x9e37_79b9_7f4a_7c15)
+    }
+
+    fn next(&mut self) -> u64 {
+        self.0 ^= self.0 >> 12;
+        self.0 ^= self.0 << 25;
+        self.0 ^= self.0 >> 27;
+        self.0.wrapping_mul(0x2545_f491_4f6c_dd1d)
+    }
+
+    fn below(&mut self, n: usize) -> usize {
+        (self.next() % n as u64) as usize
+    }
+}
+
+const FAULT_ATTEMPTS: usize = 1000;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InjectedFault {
+    pub position: usize,
+    pub value: usize,
+    // what `find_contiguous_range` returns for `value`
+    pub range: Range<usize>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct XmasStream {
+    pub preamble: usize,
+    pub numbers: Vec<usize>,
+    pub faults: Vec<InjectedFault>,
+}
+
+impl XmasStream {
+    // one number per line, as read by `input_generator`
+    pub fn to_input(&self) -> String {
+        self.numbers.iter()
+            .map(|n| n.to_string())
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct XmasEncoder {
+    rng: XorShift,
+    preamble: usize,
+}
+
+impl XmasEncoder {
+    pub fn new(seed: u64, preamble: usize) -> XmasEncoder {
+        XmasEncoder {
+            rng: XorShift::new(seed),
+            preamble,
+        }
+    }
+
+    // a valid number: the sum of two different numbers from the smaller half of the window
+    fn next_valid(&mut self, window: &[usize]) -> Option<usize> {
+        let mut sorted = window.to_vec();
+        sorted.sort_unstable();
+        sorted.dedup();
+        if sorted.len() < 2 {
+            return None;
+        }
+
+        let half = std::cmp::min(sorted.len(), std::cmp::max(2, sorted.len() / 2));
+        let i = self.rng.below(half);
+        let j = (i + 1 + self.rng.below(half - 1)) % half;
+        sorted[i].checked_add(sorted[j])
+    }
+
+    // an invalid number equal to the sum of a contiguous run before `numbers.len()`, and not
+    // already in the window, so the windows after it still hold two different numbers
+    fn next_fault(&mut self, numbers: &[usize], validator: &XmasValidator) -> Option<usize> {
+        let window = &numbers[numbers.len() - self.preamble..];
+        for _ in 0..FAULT_ATTEMPTS {
+            let len = 2 + self.rng.below(std::cmp::min(4, numbers.len() - 1));
+            let start = self.rng.below(numbers.len() - len + 1);
+
+            // a run that overflows is just another unusable pick
+            let value = match numbers[start..start + len].iter().try_fold(0usize, |sum, n| sum.checked_add(*n)) {
+                Some(value) => value,
+                None => continue,
+            };
+            if !validator.is_valid(value) && !window.contains(&value) {
+                return Some(value);
+            }
+        }
+
+        None
+    }
+
+    // `len` numbers with an invalid one at each of `fault_positions`
+    pub fn encode(&mut self, len: usize, fault_positions: &[usize]) -> Result<XmasStream, String> {
+        if self.preamble < 2 {
+            return Err(format!("Preamble={} needs at least two numbers", self.preamble));
+        }
+
+        if let Some(position) = fault_positions.iter().find(|p| **p < self.preamble || **p >= len) {
+            return Err(format!("Fault position={} is outside {}..{}", position, self.preamble, len));
+        }
+
+        let mut numbers = Vec::with_capacity(len);
+        let mut validator = XmasValidator::new(self.preamble);
+        let mut faults = Vec::new();
+
+        // distinct so that every window has a pair to sum
+        let mut preamble = (1..=self.preamble * 4).collect::<Vec<_>>();
+        for i in 0..self.preamble {
+            let j = i + self.rng.below(preamble.len() - i);
+            preamble.swap(i, j);
+        }
+
+        for position in 0..len {
+            let value = if position < self.preamble {
+                preamble[position]
+            }
+            else if fault_positions.contains(&position) {
+                let value = self.next_fault(&numbers, &validator)
+                    .ok_or_else(|| format!("No fault value found for position={}", position))?;
+                let range = find_contiguous_range(&numbers, value).unwrap();
+
+                faults.push(InjectedFault { position, value, range });
+                value
+            }
+            else {
+                let window = &numbers[position - self.preamble..];
+                self.next_valid(window)
+                    .ok_or_else(|| format!("No valid number for position={}", position))?
+            };
+
+            validator.push(value);
+            numbers.push(value);
+        }
+
+        Ok(XmasStream {
+            preamble: self.preamble,
+            numbers,
+            faults,
+        })
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    const GIVEN_INPUT: &str = "35\n20\n15\n25\n47\n40\n62\n55\n65\n95\n102\n117\n150\n182\n127\n219\n299\n277\n309\n576";
+
     #[test]
     fn solve_given_input_part1() {
         let input = "35\n\
@@ -131,4 +375,118 @@ mod tests {
         let min_max = find_contiguous_weak_sum(&weak_value, &encoded);
         assert_eq!(min_max, Some((15, 47)));        
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn validator_reports_every_invalid_number() {
+        let encoded = input_generator(GIVEN_INPUT);
+
+        assert_eq!(
+            find_invalid_numbers(&encoded, 5),
+            vec![InvalidNumber { position: 14, value: 127 }]);
+
+        // with a shorter window more numbers fail
+        assert_eq!(
+            find_invalid_numbers(&encoded, 4).iter().map(|n| n.position).collect::<Vec<_>>(),
+            vec![4, 7, 8, 10, 11, 12, 13, 14, 15, 16, 17, 18]);
+    }
+
+    #[test]
+    fn validator_streaming() {
+        let mut validator = XmasValidator::new(3);
+
+        assert_eq!(validator.push(1), Verdict::Preamble);
+        assert_eq!(validator.push(2), Verdict::Preamble);
+        assert_eq!(validator.push(2), Verdict::Preamble);
+        // 2 + 2 uses the same number twice
+        assert_eq!(validator.push(4), Verdict::Invalid);
+        assert_eq!(validator.push(6), Verdict::Valid);
+        // the 1 has left the window
+        assert_eq!(validator.push(3), Verdict::Invalid);
+        assert_eq!(validator.position(), 6);
+
+        assert!(validator.is_valid(10));
+        assert!(!validator.is_valid(12));
+    }
+
+    #[test]
+    fn contiguous_range_search() {
+        let encoded = input_generator(GIVEN_INPUT);
+
+        assert_eq!(find_contiguous_range(&encoded, 127), Some(2..6));
+        // a single number on its own is not a range
+        assert_eq!(find_contiguous_range(&encoded, 20), None);
+        assert_eq!(find_contiguous_range(&encoded, 55), Some(0..2));
+        assert_eq!(find_contiguous_range(&[], 1), None);
+
+        assert_eq!(find_contiguous_range(&[usize::MAX, usize::MAX, 2, 3], 5), Some(2..4));
+        assert_eq!(find_contiguous_range(&[1, usize::MAX - 1, 1], usize::MAX), Some(0..2));
+    }
+
+    #[test]
+    fn encoder_injects_known_faults() {
+        let stream = XmasEncoder::new(2020, 25).encode(1000, &[40, 500, 999]).unwrap();
+        let encoded = input_generator(&stream.to_input());
+        assert_eq!(encoded, stream.numbers);
+
+        assert_eq!(
+            find_invalid_numbers(&encoded, 25),
+            stream.faults.iter()
+                .map(|f| InvalidNumber { position: f.position, value: f.value })
+                .collect::<Vec<_>>());
+
+        for fault in stream.faults.iter() {
+            assert_eq!(find_contiguous_range(&encoded, fault.value), Some(fault.range.clone()));
+            assert_eq!(encoded[fault.range.clone()].iter().sum::<usize>(), fault.value);
+        }
+
+        assert_eq!(XmasEncoder::new(2020, 25).encode(1000, &[40, 500, 999]), Ok(stream));
+    }
+
+    #[test]
+    fn encoder_without_faults() {
+        let stream = XmasEncoder::new(7, 5).encode(60, &[]).unwrap();
+        assert_eq!(stream.numbers.len(), 60);
+        assert_eq!(find_weak_value(&stream.numbers, 5), None);
+
+        assert!(XmasEncoder::new(7, 5).encode(60, &[3]).is_err());
+        assert!(XmasEncoder::new(7, 1).encode(60, &[]).is_err());
+        // windows this short double quickly
+        assert!(XmasEncoder::new(7, 2).encode(1000, &[]).is_err());
+    }
+
+    #[test]
+    fn encoder_short_preamble() {
+        let mut encoder = XmasEncoder::new(3, 2);
+        assert_eq!(encoder.next_valid(&[5, 5]), None);
+        assert_eq!(encoder.next_valid(&[5, 8]), Some(13));
+
+        // a fault repeating the last number would leave a window with nothing to sum
+        for seed in 0..50 {
+            let stream = XmasEncoder::new(seed, 2).encode(20, &[3, 9]).unwrap();
+            assert_eq!(
+                find_invalid_numbers(&stream.numbers, 2).iter().map(|n| n.position).collect::<Vec<_>>(),
+                vec![3, 9]);
+        }
+    }
+
+    #[test]
+    fn encoder_skips_overflowing_runs() {
+        let numbers = [usize::MAX, 1, 2, 3];
+        let mut validator = XmasValidator::new(2);
+        validator.validate(numbers[2..].to_vec());
+
+        // 1 + 2 + 3 is the only run that neither overflows nor is valid or in the window
+        for seed in 1..20 {
+            assert_eq!(XmasEncoder::new(seed, 2).next_fault(&numbers, &validator), Some(6));
+        }
+    }
+
+    #[test]
+    fn huge_numbers_do_not_overflow() {
+        let mut validator = XmasValidator::new(2);
+        validator.validate(vec![usize::MAX, usize::MAX - 1, 1]);
+
+        assert!(validator.is_valid(usize::MAX));
+        assert_eq!(validator.push(usize::MAX - 1), Verdict::Invalid);
+    }
+}
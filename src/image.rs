@@ -0,0 +1,55 @@
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ImageFormat {
+    // greyscale, one value per pixel
+    Pgm,
+    // colour, "r g b" per pixel
+    Ppm,
+}
+
+impl ImageFormat {
+    pub fn extension(&self) -> &'static str {
+        match self {
+            ImageFormat::Pgm => "pgm",
+            ImageFormat::Ppm => "ppm",
+        }
+    }
+
+    fn magic(&self) -> &'static str {
+        match self {
+            ImageFormat::Pgm => "P2",
+            ImageFormat::Ppm => "P3",
+        }
+    }
+}
+
+// plain (ASCII) PGM or PPM of a `columns`x`rows` grid, one `scale`x`scale` block per cell;
+// `pixel(row, column)` gives a cell's value in `format`, with a maximum of 255
+pub fn render_grid<F: Fn(usize, usize) -> &'static str>(format: ImageFormat, rows: usize, columns: usize, scale: usize, pixel: F) -> String {
+    let (width, height) = (columns * scale, rows * scale);
+
+    let mut out = format!("{}\n{} {}\n255\n", format.magic(), width, height);
+    for y in 0..height {
+        let pixels = (0..width)
+            .map(|x| pixel(y / scale, x / scale))
+            .collect::<Vec<_>>();
+
+        out += &pixels.join(" ");
+        out.push('\n');
+    }
+
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn scaled_blocks() {
+        let checkers = |row: usize, column: usize| if (row + column) % 2 == 0 { "0" } else { "255" };
+
+        assert_eq!(render_grid(ImageFormat::Pgm, 1, 2, 2, checkers), "P2\n4 2\n255\n0 0 255 255\n0 0 255 255\n");
+        assert_eq!(render_grid(ImageFormat::Ppm, 1, 1, 1, |_, _| "1 2 3"), "P3\n1 1\n255\n1 2 3\n");
+        assert_eq!(render_grid(ImageFormat::Pgm, 0, 0, 3, checkers), "P2\n0 0\n255\n");
+    }
+}
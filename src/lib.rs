@@ -9,6 +9,11 @@ extern crate lazy_static;
 extern crate regex;
 extern crate bit_vec;
 
+pub mod automaton;
+pub mod biguint;
+pub mod image;
+pub mod modular;
+
 pub mod day1;
 pub mod day2;
 pub mod day3;
@@ -0,0 +1,187 @@
+use std::error::Error;
+use std::fmt;
+
+// `x ≡ residue (mod modulus)`, with `0 <= residue < modulus`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Congruence {
+    pub residue: i128,
+    pub modulus: i128,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CrtError {
+    InvalidModulus(i128),
+    // the two congruences disagree on their common factor
+    NoSolution(Congruence, Congruence),
+    Overflow,
+}
+
+impl fmt::Display for CrtError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            CrtError::InvalidModulus(m) => write!(f, "Invalid modulus={}", m),
+            CrtError::NoSolution(a, b) => write!(f, "No x with x = {} (mod {}) and x = {} (mod {})", a.residue, a.modulus, b.residue, b.modulus),
+            CrtError::Overflow => write!(f, "Combined modulus does not fit in an i128"),
+        }
+    }
+}
+
+impl Error for CrtError {}
+
+// (g, x, y) with `a * x + b * y == g == gcd(a, b)` and `g >= 0`
+pub fn extended_gcd(a: i128, b: i128) -> (i128, i128, i128) {
+    let (mut old_r, mut r) = (a, b);
+    let (mut old_x, mut x) = (1, 0);
+    let (mut old_y, mut y) = (0, 1);
+
+    while r != 0 {
+        let q = old_r / r;
+        let (next_r, next_x, next_y) = (old_r - q * r, old_x - q * x, old_y - q * y);
+        old_r = r;
+        r = next_r;
+        old_x = x;
+        x = next_x;
+        old_y = y;
+        y = next_y;
+    }
+
+    if old_r < 0 {
+        (-old_r, -old_x, -old_y)
+    }
+    else {
+        (old_r, old_x, old_y)
+    }
+}
+
+pub fn gcd(a: i128, b: i128) -> i128 {
+    extended_gcd(a, b).0
+}
+
+// `a * b mod m` without overflowing, for `m > 0`
+pub fn mul_mod(a: i128, b: i128, m: i128) -> i128 {
+    let (a, mut b) = (a.rem_euclid(m), b.rem_euclid(m));
+    if let Some(p) = a.checked_mul(b) {
+        return p % m;
+    }
+
+    // double and add, every intermediate stays below 2m
+    let mut result = 0;
+    let mut base = a;
+    while b > 0 {
+        if b & 1 == 1 {
+            result = (result - m + base).rem_euclid(m);
+        }
+        base = (base - m + base).rem_euclid(m);
+        b >>= 1;
+    }
+    result
+}
+
+pub fn mod_inverse(a: i128, m: i128) -> Option<i128> {
+    if m <= 0 {
+        return None;
+    }
+
+    match extended_gcd(a.rem_euclid(m), m) {
+        (1, x, _) => Some(x.rem_euclid(m)),
+        _ => None,
+    }
+}
+
+impl Congruence {
+    pub fn new(residue: i128, modulus: i128) -> Result<Congruence, CrtError> {
+        if modulus <= 0 {
+            return Err(CrtError::InvalidModulus(modulus));
+        }
+
+        Ok(Congruence { residue: residue.rem_euclid(modulus), modulus })
+    }
+
+    // matches every integer
+    pub fn any() -> Congruence {
+        Congruence { residue: 0, modulus: 1 }
+    }
+
+    pub fn contains(&self, x: i128) -> bool {
+        x.rem_euclid(self.modulus) == self.residue
+    }
+
+    // the congruence satisfied by exactly the common solutions, the moduli need not be coprime
+    pub fn combine(&self, other: &Congruence) -> Result<Congruence, CrtError> {
+        let (g, _, _) = extended_gcd(self.modulus, other.modulus);
+        let diff = other.residue - self.residue;
+        if diff % g != 0 {
+            return Err(CrtError::NoSolution(*self, *other));
+        }
+
+        // x = r1 + m1 * t with m1 * t ≡ diff (mod m2), divided through by g
+        let m2 = other.modulus / g;
+        let inverse = mod_inverse(self.modulus / g, m2).unwrap();
+        let t = mul_mod(diff / g, inverse, m2);
+
+        let modulus = (self.modulus / g).checked_mul(other.modulus).ok_or(CrtError::Overflow)?;
+        let residue = mul_mod(self.modulus, t, modulus) + self.residue;
+        Congruence::new(residue, modulus)
+    }
+}
+
+// folds a system of congruences into one, `Congruence::any()` for an empty system
+pub fn solve_congruences<I: IntoIterator<Item = Congruence>>(congruences: I) -> Result<Congruence, CrtError> {
+    congruences.into_iter()
+        .try_fold(Congruence::any(), |acc, c| acc.combine(&c))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn c(residue: i128, modulus: i128) -> Congruence {
+        Congruence::new(residue, modulus).unwrap()
+    }
+
+    #[test]
+    fn gcd_and_inverse() {
+        let (g, x, y) = extended_gcd(240, 46);
+        assert_eq!(g, 2);
+        assert_eq!(240 * x + 46 * y, 2);
+
+        assert_eq!(extended_gcd(-12, 18).0, 6);
+        assert_eq!(gcd(0, 7), 7);
+        assert_eq!(mod_inverse(3, 11), Some(4));
+        assert_eq!(mod_inverse(-3, 11), Some(7));
+        assert_eq!(mod_inverse(6, 9), None);
+    }
+
+    #[test]
+    fn big_products() {
+        let m = i128::MAX - 1;
+        assert_eq!(mul_mod(m - 1, m - 1, m), 1);
+        assert_eq!(mul_mod(-1, 5, 7), 2);
+    }
+
+    #[test]
+    fn coprime_system() {
+        let system = vec![c(0, 3), c(3, 4), c(4, 5)];
+        assert_eq!(solve_congruences(system), Ok(c(39, 60)));
+        assert_eq!(solve_congruences(vec![]), Ok(Congruence::any()));
+    }
+
+    #[test]
+    fn shared_factors() {
+        assert_eq!(c(2, 4).combine(&c(4, 6)), Ok(c(10, 12)));
+        assert_eq!(c(3, 6).combine(&c(3, 6)), Ok(c(3, 6)));
+        assert_eq!(c(1, 4).combine(&c(2, 6)), Err(CrtError::NoSolution(c(1, 4), c(2, 6))));
+    }
+
+    #[test]
+    fn large_moduli() {
+        let (p, q) = (1_000_000_000_000_000_003, 999_999_999_999_999_989);
+        let x = 123_456_789_012_345_678_901_234_567_890;
+        let solved = solve_congruences(vec![c(x, p), c(x, q)]).unwrap();
+
+        assert_eq!(solved.modulus, p * q);
+        assert!(solved.contains(x));
+        assert_eq!(c(0, i128::MAX).combine(&c(0, 2)), Err(CrtError::Overflow));
+        assert_eq!(Congruence::new(1, 0), Err(CrtError::InvalidModulus(0)));
+    }
+}